@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::{
+    board::{Board, CellSet, Piece},
+    deadlock::DeadlockDetector,
+    reverse_solver::get_combinations,
+    solve::{solve_shortest, Move, Solution},
+};
+
+// Most solved boards the backward side starts from. Boards with more, from many pieces without a
+// goal that could end up on many cells, are searched forwards only.
+const MAX_SOLVED_BOARDS: usize = 100_000;
+
+// board -> (depth, neighbouring board one step closer to where that side started, move between them)
+type Visited<const WORDS: usize, const PLANES: usize> =
+    HashMap<Board<WORDS, PLANES>, (u16, Option<(Board<WORDS, PLANES>, Move)>)>;
+
+// Searches forwards from `board` and backwards from every solved board it could end up as, one
+// whole layer at a time from whichever side has the smaller frontier. The first board reached
+// from both sides lies on a shortest solution. Falls back to solve_shortest when there are too
+// many solved boards to start from.
+pub fn solve_bidirectional<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+) -> Solution {
+    if board.is_solved() {
+        return Solution {
+            moves: Vec::new(),
+            move_count: 0,
+            tree_size: 1,
+            can_be_solved: true,
+        };
+    }
+
+    // Only the forward side can meet dead boards, the backward side starts from solved ones. It can
+    // meet boards the start never leads to though, so it drops any with more pieces of a kind than
+    // the start has, as moves never add pieces, or with a piece where no piece of its kind can go.
+    let deadlocks = DeadlockDetector::new(&board);
+    let max_counts = Piece::HERDS.map(|(mover, _)| board.count_piece(mover));
+    let reachable = deadlocks.reachable_from(&board);
+    let could_meet = |board: &Board<WORDS, PLANES>| {
+        reachable
+            .iter()
+            .zip(max_counts)
+            .all(|(&(mover, cells), max_count)| {
+                let pieces = board.piece_mask(mover);
+                pieces.len() <= max_count && (pieces & !cells).is_empty()
+            })
+    };
+    let mut forward: Visited<WORDS, PLANES> = HashMap::new();
+    let mut forward_frontier = vec![board];
+    forward.insert(board, (0, None));
+
+    let mut backward: Visited<WORDS, PLANES> = HashMap::new();
+    let mut backward_frontier: Vec<Board<WORDS, PLANES>> = match solved_boards(&board, &reachable) {
+        Some(solved_boards) => solved_boards
+            .into_iter()
+            .filter(|solved_board| could_meet(solved_board))
+            .collect(),
+        None => return solve_shortest(board, false),
+    };
+    for solved_board in &backward_frontier {
+        backward.insert(*solved_board, (0, None));
+    }
+
+    // boards expanded and new boards found on each side, to estimate what the next layer will cost
+    let mut forward_branching = (1, 1);
+    let mut backward_branching = (1, 1);
+
+    while !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+        let forward_cost = forward_frontier.len() * forward_branching.1 / forward_branching.0;
+        let backward_cost = backward_frontier.len() * backward_branching.1 / backward_branching.0;
+        let meeting = if forward_cost <= backward_cost {
+            let mut next_frontier = Vec::new();
+            let mut meeting = None;
+            for board in &forward_frontier {
+                let depth = forward[board].0 + 1;
+                for (next_board, from, to, puller) in board.get_possible_moves() {
+                    if forward.contains_key(&next_board) || deadlocks.is_dead(&next_board) {
+                        continue;
+                    }
+                    forward.insert(
+                        next_board,
+                        (depth, Some((*board, Move { from, to, puller }))),
+                    );
+                    if meeting.is_none() && backward.contains_key(&next_board) {
+                        meeting = Some(next_board);
+                    }
+                    next_frontier.push(next_board);
+                }
+            }
+            forward_branching.0 += forward_frontier.len();
+            forward_branching.1 += next_frontier.len();
+            forward_frontier = next_frontier;
+            meeting
+        } else {
+            let mut next_frontier = Vec::new();
+            let mut meeting = None;
+            for board in &backward_frontier {
+                let depth = backward[board].0 + 1;
+                for (previous_board, from, to, puller) in board.get_possible_previous_boards() {
+                    if backward.contains_key(&previous_board) || !could_meet(&previous_board) {
+                        continue;
+                    }
+                    backward.insert(
+                        previous_board,
+                        (depth, Some((*board, Move { from, to, puller }))),
+                    );
+                    if meeting.is_none() && forward.contains_key(&previous_board) {
+                        meeting = Some(previous_board);
+                    }
+                    next_frontier.push(previous_board);
+                }
+            }
+            backward_branching.0 += backward_frontier.len();
+            backward_branching.1 += next_frontier.len();
+            backward_frontier = next_frontier;
+            meeting
+        };
+
+        // Neither side had reached the other before this layer, so every meeting in it is
+        // exactly one move longer than the two depths searched so far: any of them is optimal.
+        if let Some(meeting) = meeting {
+            let mut moves = Vec::new();
+            let mut board = &meeting;
+            while let Some((_, Some((previous_board, m)))) = forward.get(board) {
+                moves.push(m.clone());
+                board = previous_board;
+            }
+            moves.reverse();
+            board = &meeting;
+            while let Some((_, Some((next_board, m)))) = backward.get(board) {
+                moves.push(m.clone());
+                board = next_board;
+            }
+            return Solution {
+                move_count: moves.len() as u16,
+                moves,
+                tree_size: forward.len() + backward.len(),
+                can_be_solved: true,
+            };
+        }
+    }
+
+    Solution {
+        moves: Vec::new(),
+        move_count: 0,
+        tree_size: forward.len() + backward.len(),
+        can_be_solved: false,
+    }
+}
+
+// Every solved board `board` could turn into: goals and empty cells stay where they are, pieces
+// that need a home have all gone home, and the others can be on any free cell their kind can
+// reach, as `reachable` gives it for each kind. None if that could be more than MAX_SOLVED_BOARDS.
+pub fn solved_boards<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+    reachable: &[(Piece, CellSet<WORDS>)],
+) -> Option<Vec<Board<WORDS, PLANES>>> {
+    let mut base = *board;
+    for i in 0..base.cell_count() {
+        if base.get_index(i).is_mover() {
+            base.set_index(i, Piece::Blank);
+        }
+    }
+
+    let stuck_herds: Vec<(Piece, usize, CellSet<WORDS>)> = reachable
+        .iter()
+        .filter(|&&(mover, _)| !board.needs_home(mover))
+        .map(|&(mover, cells)| (mover, board.count_piece(mover), cells))
+        .filter(|&(_, count, _)| count > 0)
+        .collect();
+    // an upper bound, as pieces of different kinds can't share a cell
+    let board_count = stuck_herds
+        .iter()
+        .fold(1, |total: usize, (_, count, cells)| {
+            let candidates = (base.piece_mask(Piece::Blank) & *cells).len();
+            total.saturating_mul(combination_count(candidates, *count))
+        });
+    if board_count > MAX_SOLVED_BOARDS {
+        return None;
+    }
+
+    let mut boards = vec![base];
+    for (stuck_piece, stuck_count, cells) in stuck_herds {
+        let mut with_piece = Vec::new();
+        for board in boards {
+            let blank_indices: Vec<u16> = (board.piece_mask(Piece::Blank) & cells).iter().collect();
+            let mut combinations: Vec<Vec<u16>> = Vec::new();
+            get_combinations(
+                &mut combinations,
+                &blank_indices,
+                stuck_count,
+                0,
+                Vec::new(),
+            );
+            for combination in combinations {
+                let mut solved_board = board;
+                for index in combination {
+                    solved_board.set_index(index as usize, stuck_piece);
+                }
+                with_piece.push(solved_board);
+            }
+        }
+        boards = with_piece;
+    }
+    Some(boards)
+}
+
+// the number of ways to pick `k` of `n` things, or usize::MAX if that doesn't fit
+fn combination_count(n: usize, k: usize) -> usize {
+    if k > n {
+        return 0;
+    }
+    (0..k)
+        .try_fold(1usize, |count, i| {
+            count.checked_mul(n - i).map(|count| count / (i + 1))
+        })
+        .unwrap_or(usize::MAX)
+}
@@ -1,6 +1,9 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{BitAnd, BitOr, Not};
+use std::str::FromStr;
 
-use crate::settings::HEXAGONAL_MODE;
+use crate::solve::Move;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 #[repr(u8)]
@@ -11,231 +14,540 @@ pub enum Piece {
     Barn,
     Empty,
     Blank,
+    // not part of the board, for boards that aren't rectangles; to pieces it is past the edge
+    Outside,
+    // herds of coloured cows, which only go into barns of their own colour
+    RedCow,
+    RedBarn,
+    GreenCow,
+    GreenBarn,
 }
 
-#[derive(Clone, Eq, Hash, PartialEq)]
-pub struct Board {
+impl Piece {
+    // indexed by the code a piece is packed into a Board as
+    pub(crate) const ALL: [Piece; 11] = [
+        Piece::Cow,
+        Piece::Person,
+        Piece::House,
+        Piece::Barn,
+        Piece::Empty,
+        Piece::Blank,
+        Piece::Outside,
+        Piece::RedCow,
+        Piece::RedBarn,
+        Piece::GreenCow,
+        Piece::GreenBarn,
+    ];
+
+    // Every kind of piece that moves, with the goal it goes home to. Goals of the other kinds stop
+    // it like a piece would, so people are just one more herd as far as the rules go.
+    pub const HERDS: [(Piece, Piece); 4] = [
+        (Piece::Cow, Piece::Barn),
+        (Piece::Person, Piece::House),
+        (Piece::RedCow, Piece::RedBarn),
+        (Piece::GreenCow, Piece::GreenBarn),
+    ];
+
+    pub fn is_mover(self) -> bool {
+        self.goal().is_some()
+    }
+
+    pub fn is_goal(self) -> bool {
+        self.mover().is_some()
+    }
+
+    pub fn is_cow(self) -> bool {
+        matches!(self, Piece::Cow | Piece::RedCow | Piece::GreenCow)
+    }
+
+    // the red and green herds and their barns, which only coloured boards have room for
+    pub fn is_coloured(self) -> bool {
+        matches!(
+            self,
+            Piece::RedCow | Piece::RedBarn | Piece::GreenCow | Piece::GreenBarn
+        )
+    }
+
+    // the goal a moving piece goes home to
+    pub fn goal(self) -> Option<Piece> {
+        match self {
+            Piece::Cow => Some(Piece::Barn),
+            Piece::Person => Some(Piece::House),
+            Piece::RedCow => Some(Piece::RedBarn),
+            Piece::GreenCow => Some(Piece::GreenBarn),
+            _ => None,
+        }
+    }
+
+    // the moving piece a goal takes in
+    pub fn mover(self) -> Option<Piece> {
+        match self {
+            Piece::Barn => Some(Piece::Cow),
+            Piece::House => Some(Piece::Person),
+            Piece::RedBarn => Some(Piece::RedCow),
+            Piece::GreenBarn => Some(Piece::GreenCow),
+            _ => None,
+        }
+    }
+
+    fn from_char(c: char) -> Option<Piece> {
+        match c {
+            'O' => Some(Piece::Cow),
+            'P' => Some(Piece::Person),
+            'H' => Some(Piece::House),
+            'B' => Some(Piece::Barn),
+            'E' => Some(Piece::Empty),
+            '_' => Some(Piece::Blank),
+            '.' => Some(Piece::Outside),
+            'r' => Some(Piece::RedCow),
+            'R' => Some(Piece::RedBarn),
+            'g' => Some(Piece::GreenCow),
+            'G' => Some(Piece::GreenBarn),
+            _ => None,
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Piece::Cow => 'O',
+            Piece::Person => 'P',
+            Piece::House => 'H',
+            Piece::Barn => 'B',
+            Piece::Empty => 'E',
+            Piece::Blank => '_',
+            Piece::Outside => '.',
+            Piece::RedCow => 'r',
+            Piece::RedBarn => 'R',
+            Piece::GreenCow => 'g',
+            Piece::GreenBarn => 'G',
+        }
+    }
+}
+
+// Number of bits a piece code takes up. The pieces before the coloured herds fit in three, which
+// keeps the default Board down to 32 bytes; the coloured pieces need a fourth.
+pub const PLAIN_PLANES: usize = 3;
+pub const COLOURED_PLANES: usize = 4;
+
+// cells that fit in one word of a CellSet
+const WORD_CELLS: usize = 64;
+
+// words per bit plane of a LargeBoard, room for 32x32 cells
+pub const LARGE_BOARD_WORDS: usize = 16;
+
+// for designs too big for the default Board
+pub type LargeBoard = Board<LARGE_BOARD_WORDS>;
+
+// for designs with red or green herds
+pub type ColouredBoard = Board<1, COLOURED_PLANES>;
+pub type LargeColouredBoard = Board<LARGE_BOARD_WORDS, COLOURED_PLANES>;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Topology {
+    Rect,
+    Hex,
+    // triangles pointing up and down in turn along each row, the top left one pointing up
+    Triangle,
+}
+
+impl Topology {
+    pub const ALL: [Topology; 3] = [Topology::Rect, Topology::Hex, Topology::Triangle];
+
+    // folder the generator writes puzzles of this topology to
+    pub fn directory(&self) -> &'static str {
+        match self {
+            Topology::Rect => "rect",
+            Topology::Hex => "hex",
+            Topology::Triangle => "triangle",
+        }
+    }
+
+    // letter used to tag boards of this topology in combined puzzle lists
+    pub fn prefix(&self) -> char {
+        match self {
+            Topology::Rect => 'R',
+            Topology::Hex => 'H',
+            Topology::Triangle => 'T',
+        }
+    }
+
+    pub fn from_prefix(prefix: char) -> Option<Topology> {
+        match prefix {
+            'R' => Some(Topology::Rect),
+            'H' => Some(Topology::Hex),
+            'T' => Some(Topology::Triangle),
+            _ => None,
+        }
+    }
+
+    // Whether a board of this size can wrap. Its opposite edges have to fit together the way rows
+    // and columns next to each other do: hex rows alternate, so need an even number of them, and
+    // triangles alternate along rows and columns, so need an even number of both.
+    pub fn can_wrap(&self, width: u8, height: u8) -> bool {
+        match self {
+            Topology::Rect => true,
+            Topology::Hex => height.is_multiple_of(2),
+            Topology::Triangle => width.is_multiple_of(2) && height.is_multiple_of(2),
+        }
+    }
+
+    // directions a piece can slide in, in the order moves are generated
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            Topology::Rect => &[
+                Direction::Left,
+                Direction::Right,
+                Direction::Up,
+                Direction::Down,
+            ],
+            // on triangles, the diagonals follow the strips of triangles between two parallel lines
+            Topology::Hex | Topology::Triangle => &[
+                Direction::Left,
+                Direction::Right,
+                Direction::UpLeft,
+                Direction::UpRight,
+                Direction::DownLeft,
+                Direction::DownRight,
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+// One bit per cell of a board, bit i % 64 of word i / 64 for cell i. A Board<WORDS> packs its
+// pieces into these, and they are what piece_mask gives back.
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Debug)]
+pub struct CellSet<const WORDS: usize = 1>([u64; WORDS]);
+
+impl<const WORDS: usize> CellSet<WORDS> {
+    pub fn new() -> CellSet<WORDS> {
+        CellSet([0; WORDS])
+    }
+
+    // the cells 0 to count - 1
+    pub fn first(count: usize) -> CellSet<WORDS> {
+        let mut cells = CellSet::new();
+        for (i, word) in cells.0.iter_mut().enumerate() {
+            let bits = count.saturating_sub(i * WORD_CELLS).min(WORD_CELLS);
+            *word = if bits == WORD_CELLS {
+                u64::MAX
+            } else {
+                (1 << bits) - 1
+            };
+        }
+        cells
+    }
+
+    pub fn contains(&self, index: u16) -> bool {
+        let index = index as usize;
+        (self.0[index / WORD_CELLS] >> (index % WORD_CELLS)) & 1 == 1
+    }
+
+    pub fn insert(&mut self, index: u16) {
+        let index = index as usize;
+        self.0[index / WORD_CELLS] |= 1 << (index % WORD_CELLS);
+    }
+
+    pub fn remove(&mut self, index: u16) {
+        let index = index as usize;
+        self.0[index / WORD_CELLS] &= !(1 << (index % WORD_CELLS));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    // the cells in the set, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some((i * WORD_CELLS + bit) as u16)
+            })
+        })
+    }
+}
+
+impl<const WORDS: usize> Default for CellSet<WORDS> {
+    fn default() -> CellSet<WORDS> {
+        CellSet::new()
+    }
+}
+
+// one write for all the words, without the length an array hashes first
+impl<const WORDS: usize> Hash for CellSet<WORDS> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        u64::hash_slice(&self.0, state);
+    }
+}
+
+impl<const WORDS: usize> BitAnd for CellSet<WORDS> {
+    type Output = CellSet<WORDS>;
+
+    fn bitand(mut self, other: CellSet<WORDS>) -> CellSet<WORDS> {
+        for (word, other) in self.0.iter_mut().zip(other.0) {
+            *word &= other;
+        }
+        self
+    }
+}
+
+impl<const WORDS: usize> BitOr for CellSet<WORDS> {
+    type Output = CellSet<WORDS>;
+
+    fn bitor(mut self, other: CellSet<WORDS>) -> CellSet<WORDS> {
+        for (word, other) in self.0.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+        self
+    }
+}
+
+// every cell not in the set, including ones past the end of the board it came from
+impl<const WORDS: usize> Not for CellSet<WORDS> {
+    type Output = CellSet<WORDS>;
+
+    fn not(mut self) -> CellSet<WORDS> {
+        for word in self.0.iter_mut() {
+            *word = !*word;
+        }
+        self
+    }
+}
+
+// WORDS is the number of 64 bit words each bit plane takes, so a board holds up to 64 * WORDS
+// cells, and PLANES the number of bit planes. Searches keep millions of boards, so the default is
+// the smallest size, which fits every board up to 8x8 without coloured herds; LargeBoard is for
+// bigger designs, and ColouredBoard and LargeColouredBoard for ones with coloured herds.
+#[derive(Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct Board<const WORDS: usize = 1, const PLANES: usize = PLAIN_PLANES> {
     pub width: u8,
     pub height: u8,
-    pub pieces: Vec<Piece>,
+    pub topology: Topology,
+    // whether the edges wrap around, a piece sliding off one edge coming back on at the opposite one
+    pub wraps: bool,
+    // cell i of planes[k] is set when bit k of the code of the piece in cell i is, cells past the
+    // last are never set
+    planes: [CellSet<WORDS>; PLANES],
 }
 
-impl Board {
-    fn new(width: u8, height: u8) -> Board {
-        Board {
-            width: width,
-            height: height,
-            pieces: vec![Piece::Blank; (width * height) as usize],
+impl<const WORDS: usize, const PLANES: usize> Board<WORDS, PLANES> {
+    pub const MAX_CELLS: usize = WORDS * WORD_CELLS;
+
+    pub(crate) fn new(
+        width: u8,
+        height: u8,
+        topology: Topology,
+        wraps: bool,
+    ) -> Board<WORDS, PLANES> {
+        assert!(
+            width as usize * height as usize <= Self::MAX_CELLS,
+            "Board has more than {} cells",
+            Self::MAX_CELLS
+        );
+        let mut board = Board {
+            width,
+            height,
+            topology,
+            wraps,
+            planes: [CellSet::new(); PLANES],
+        };
+        for i in 0..board.cell_count() {
+            board.set_index(i, Piece::Blank);
         }
+        board
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width as usize * self.height as usize
     }
 
     fn get(&self, x: u8, y: u8) -> Piece {
-        self.pieces[(y * self.width + x) as usize]
+        self.get_index(y as usize * self.width as usize + x as usize)
     }
 
     fn set(&mut self, x: u8, y: u8, piece: Piece) {
-        self.pieces[(y * self.width + x) as usize] = piece;
-    }
-
-    fn set_index(&mut self, index: usize, piece: Piece) {
-        self.pieces[index] = piece;
-    }
-
-    pub fn get_moves_from(&self, index: u8) -> Vec<(u8, u8)> {
-        let mut moves: Vec<(u8, u8)> = Vec::new();
-        let x = index % self.width;
-        let y = index / self.width;
-        let piece = self.get(x, y);
-
-        // left
-        let mut new_x = x;
-        let offset = y * self.width;
-        while new_x > 0 {
-            new_x -= 1;
-            if check_move(
-                piece,
-                self.get(new_x, y),
-                &mut moves,
-                offset + new_x,
-                offset + new_x + 1,
-                x - new_x > 1,
-            ) {
-                break;
-            }
-        }
-        // right
-        new_x = x;
-        while new_x < self.width - 1 {
-            new_x += 1;
-            if check_move(
-                piece,
-                self.get(new_x, y),
-                &mut moves,
-                offset + new_x,
-                offset + new_x - 1,
-                new_x - x > 1,
-            ) {
-                break;
-            }
-        }
-        if HEXAGONAL_MODE {
-            // up left
-            let mut new_x = x;
-            let mut new_y = y;
-            while new_x + (new_y % 2) > 0 && new_y > 0 {
-                new_y -= 1;
-                new_x -= new_y % 2;
-                if check_move(
-                    piece,
-                    self.get(new_x, new_y),
-                    &mut moves,
-                    (new_y * self.width) + new_x,
-                    ((new_y + 1) * self.width) + new_x + new_y % 2,
-                    y - new_y > 1,
-                ) {
-                    break;
-                }
-            }
-            // up right
-            new_x = x;
-            new_y = y;
-            while new_x < self.width - (new_y % 2) && new_y > 0 {
-                new_y -= 1;
-                new_x += (new_y + 1) % 2;
-                if check_move(
-                    piece,
-                    self.get(new_x, new_y),
-                    &mut moves,
-                    (new_y * self.width) + new_x,
-                    ((new_y + 1) * self.width) + new_x - (new_y + 1) % 2,
-                    y - new_y > 1,
-                ) {
-                    break;
-                }
-            }
+        self.set_index(y as usize * self.width as usize + x as usize, piece);
+    }
 
-            // down left
-            new_x = x;
-            new_y = y;
-            while new_x + (new_y % 2) > 0 && new_y < self.height - 1 {
-                new_y += 1;
-                new_x -= new_y % 2;
-                if check_move(
-                    piece,
-                    self.get(new_x, new_y),
-                    &mut moves,
-                    (new_y * self.width) + new_x,
-                    ((new_y - 1) * self.width) + new_x + new_y % 2,
-                    new_y - y > 1,
-                ) {
-                    break;
-                }
+    pub fn get_index(&self, index: usize) -> Piece {
+        let mut code = 0;
+        for (bit, plane) in self.planes.iter().enumerate() {
+            code |= (plane.contains(index as u16) as usize) << bit;
+        }
+        Piece::ALL[code]
+    }
+
+    // whether this kind of board has the planes to hold `piece`
+    pub fn can_hold(piece: Piece) -> bool {
+        !piece.is_coloured() || PLANES >= COLOURED_PLANES
+    }
+
+    pub fn set_index(&mut self, index: usize, piece: Piece) {
+        debug_assert!(Self::can_hold(piece), "{:?} needs a coloured board", piece);
+        let code = piece as usize;
+        for (bit, plane) in self.planes.iter_mut().enumerate() {
+            if (code >> bit) & 1 == 1 {
+                plane.insert(index as u16);
+            } else {
+                plane.remove(index as u16);
             }
+        }
+    }
 
-            // down right
-            new_x = x;
-            new_y = y;
-            while new_x < self.width - (new_y % 2) && new_y < self.height - 1 {
-                new_y += 1;
-                new_x += (new_y + 1) % 2;
-                if check_move(
-                    piece,
-                    self.get(new_x, new_y),
-                    &mut moves,
-                    (new_y * self.width) + new_x,
-                    ((new_y - 1) * self.width) + new_x - (new_y + 1) % 2,
-                    new_y - y > 1,
-                ) {
-                    break;
-                }
+    // the cells holding `piece`, none if it is a piece this kind of board can't hold
+    pub fn piece_mask(&self, piece: Piece) -> CellSet<WORDS> {
+        if !Self::can_hold(piece) {
+            return CellSet::new();
+        }
+        let code = piece as usize;
+        let mut mask = CellSet::first(self.cell_count());
+        for (bit, plane) in self.planes.iter().enumerate() {
+            mask = mask
+                & if (code >> bit) & 1 == 1 {
+                    *plane
+                } else {
+                    !*plane
+                };
+        }
+        mask
+    }
+
+    // index of the cell next to `index` in `direction`, or None past the edge of a board that
+    // doesn't wrap or when that cell is outside the board
+    pub fn neighbour(&self, index: u16, direction: Direction) -> Option<u16> {
+        // rect boards have no diagonals, and hex and triangle ones no straight up or down
+        if !self.topology.directions().contains(&direction) {
+            return None;
+        }
+        let width = self.width as i32;
+        let x = index as i32 % width;
+        let y = index as i32 / width;
+        let (dx, dy) = if self.topology == Topology::Triangle {
+            // A diagonal strip zigzags: it crosses a triangle's slanted side into its neighbour in
+            // the row, then that one's flat side into the next row. An up triangle's flat side is
+            // at the bottom, a down triangle's at the top.
+            let up = (x + y) % 2 == 0;
+            match direction {
+                Direction::Left => (-1, 0),
+                Direction::Right => (1, 0),
+                Direction::UpLeft if up => (-1, 0),
+                Direction::UpRight if up => (1, 0),
+                Direction::DownLeft if !up => (-1, 0),
+                Direction::DownRight if !up => (1, 0),
+                Direction::UpLeft | Direction::UpRight => (0, -1),
+                Direction::DownLeft | Direction::DownRight => (0, 1),
+                Direction::Up | Direction::Down => return None,
             }
         } else {
-            // up
-            let mut new_y = y;
-            while new_y > 0 {
-                new_y -= 1;
-                if check_move(
-                    piece,
-                    self.get(x, new_y),
-                    &mut moves,
-                    (new_y * self.width) + x,
-                    ((new_y + 1) * self.width) + x,
-                    y - new_y > 1,
-                ) {
-                    break;
-                }
+            // hex rows alternate, odd rows are shifted half a cell to the right
+            let odd_row = y % 2;
+            match direction {
+                Direction::Left => (-1, 0),
+                Direction::Right => (1, 0),
+                Direction::Up => (0, -1),
+                Direction::Down => (0, 1),
+                Direction::UpLeft => (odd_row - 1, -1),
+                Direction::UpRight => (odd_row, -1),
+                Direction::DownLeft => (odd_row - 1, 1),
+                Direction::DownRight => (odd_row, 1),
             }
-            // down
-            new_y = y;
-            while new_y < self.height - 1 {
-                new_y += 1;
+        };
+        let mut new_x = x + dx;
+        let mut new_y = y + dy;
+        if self.wraps {
+            new_x = new_x.rem_euclid(width);
+            new_y = new_y.rem_euclid(self.height as i32);
+        }
+        if new_x < 0 || new_y < 0 || new_x >= width || new_y >= self.height as i32 {
+            return None;
+        }
+        let new_index = (new_y * width + new_x) as u16;
+        if self.get_index(new_index as usize) == Piece::Outside {
+            return None;
+        }
+        Some(new_index)
+    }
+
+    // cells a piece at `index` passes over when sliding in `direction`, nearest first, ending at the
+    // edge or, on a board that wraps, before coming back round to `index`
+    pub fn ray(&self, index: u16, direction: Direction) -> Ray<'_, WORDS, PLANES> {
+        Ray {
+            board: self,
+            start: index,
+            index,
+            direction,
+        }
+    }
+
+    pub fn get_moves_from(&self, index: u16) -> Vec<(u16, u16)> {
+        let mut moves: Vec<(u16, u16)> = Vec::new();
+        let piece = self.get_index(index as usize);
+
+        for &direction in self.topology.directions() {
+            let mut prev_space = index;
+            for space in self.ray(index, direction) {
                 if check_move(
                     piece,
-                    self.get(x, new_y),
+                    self.get_index(space as usize),
                     &mut moves,
-                    (new_y * self.width) + x,
-                    ((new_y - 1) * self.width) + x,
-                    new_y - y > 1,
+                    space,
+                    prev_space,
+                    prev_space != index,
                 ) {
                     break;
                 }
+                prev_space = space;
             }
         }
 
         moves
     }
 
-    pub fn get_reverse_moves(&self, index: u8) -> Vec<(u8, u8, u8, Piece)> {
+    pub fn get_reverse_moves(&self, index: u16) -> Vec<(u16, u16, u16, Piece)> {
         // returns (from, to, puller, Piece)
-        let mut reverse_moves: Vec<(u8, u8, u8, Piece)> = Vec::new();
-        let x = index % self.width;
-        let y = index / self.width;
-        let this_piece = self.get(x, y);
-        if this_piece == Piece::Blank || this_piece == Piece::Empty {
+        let mut reverse_moves: Vec<(u16, u16, u16, Piece)> = Vec::new();
+        let this_piece = self.get_index(index as usize);
+        if this_piece == Piece::Blank || this_piece == Piece::Empty || this_piece == Piece::Outside
+        {
             return reverse_moves;
         }
 
-        // left
-        if x > 0 {
-            let mut new_x = x - 1;
-            let new_y = y;
-            let adjacent_index = (new_y * self.width) + new_x;
-            let mut direction_indices: Vec<u8> = Vec::new();
-            if self.pieces[adjacent_index as usize] == Piece::Blank {
-                direction_indices.push(adjacent_index);
-            }
-            while new_x > 0 {
-                new_x -= 1;
-                if self.get(new_x, new_y) != Piece::Blank {
-                    break;
-                }
-                direction_indices.push((new_y * self.width) + new_x);
-            }
-
-            self.check_reverse_direction(
-                &mut reverse_moves,
-                this_piece,
-                index,
-                adjacent_index,
-                direction_indices,
-            );
-        }
-
-        // right
-        if x < self.width - 1 {
-            let mut new_x = x + 1;
-            let new_y = y;
-            let adjacent_index = (new_y * self.width) + new_x;
-            let mut direction_indices: Vec<u8> = Vec::new();
-            if self.pieces[adjacent_index as usize] == Piece::Blank {
+        for &direction in self.topology.directions() {
+            let mut ray = self.ray(index, direction);
+            let adjacent_index = match ray.next() {
+                Some(adjacent_index) => adjacent_index,
+                None => continue,
+            };
+            let mut direction_indices: Vec<u16> = Vec::new();
+            if self.get_index(adjacent_index as usize) == Piece::Blank {
                 direction_indices.push(adjacent_index);
             }
-            while new_x < self.width - 1 {
-                new_x += 1;
-                if self.get(new_x, new_y) != Piece::Blank {
+            for space in ray {
+                if self.get_index(space as usize) != Piece::Blank {
                     break;
                 }
-                direction_indices.push((new_y * self.width) + new_x);
+                direction_indices.push(space);
             }
 
             self.check_reverse_direction(
@@ -247,248 +559,109 @@ impl Board {
             );
         }
 
-        if HEXAGONAL_MODE {
-            // up left
-            if x + (y % 2) > 0 && y > 0 {
-                let mut new_y = y - 1;
-                let mut new_x = x - (new_y % 2);
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_x + (new_y % 2) > 0 && new_y > 0 {
-                    new_y -= 1;
-                    new_x -= new_y % 2;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-
-            // up right
-            if x < self.width - (y % 2) && y > 0 {
-                let mut new_x = x + (y % 2);
-                let mut new_y = y - 1;
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_x < self.width - (new_y % 2) && new_y > 0 {
-                    new_x += new_y % 2;
-                    new_y -= 1;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-
-            // down left
-            if x + (y % 2) > 0 && y < self.height - 1 {
-                let mut new_y = y + 1;
-                let mut new_x = x - (new_y % 2);
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_x + (new_y % 2) > 0 && new_y < self.height - 1 {
-                    new_y += 1;
-                    new_x -= new_y % 2;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-
-            // down right
-            if x < self.width - (y % 2) && y < self.height - 1 {
-                let mut new_x = x + (y % 2);
-                let mut new_y = y + 1;
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_x < self.width - (new_y % 2) && new_y < self.height - 1 {
-                    new_x += new_y % 2;
-                    new_y += 1;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-        } else {
-            // up
-            if y > 0 {
-                let new_x = x;
-                let mut new_y = y - 1;
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_y > 0 {
-                    new_y -= 1;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-            // down
-            if y < self.height - 1 {
-                let new_x = x;
-                let mut new_y = y + 1;
-                let adjacent_index = (new_y * self.width) + new_x;
-                let mut direction_indices: Vec<u8> = Vec::new();
-                if self.pieces[adjacent_index as usize] == Piece::Blank {
-                    direction_indices.push(adjacent_index);
-                }
-                while new_y < self.height - 1 {
-                    new_y += 1;
-                    if self.get(new_x, new_y) != Piece::Blank {
-                        break;
-                    }
-                    direction_indices.push((new_y * self.width) + new_x);
-                }
-
-                self.check_reverse_direction(
-                    &mut reverse_moves,
-                    this_piece,
-                    index,
-                    adjacent_index,
-                    direction_indices,
-                );
-            }
-        }
-
         reverse_moves
     }
 
     fn check_reverse_direction(
         &self,
-        reverse_moves: &mut Vec<(u8, u8, u8, Piece)>,
+        reverse_moves: &mut Vec<(u16, u16, u16, Piece)>,
         this_piece: Piece,
-        this_index: u8,
-        adjacent_index: u8,
-        direction_indices: Vec<u8>,
+        this_index: u16,
+        adjacent_index: u16,
+        direction_indices: Vec<u16>,
     ) {
-        let adjacent_piece = self.pieces[adjacent_index as usize];
+        let adjacent_piece = self.get_index(adjacent_index as usize);
 
         // ignore unchangeable pieces
-        if adjacent_piece == Piece::Empty
-            || adjacent_piece == Piece::House
-            || adjacent_piece == Piece::Barn
-        {
+        if adjacent_piece == Piece::Empty || adjacent_piece.is_goal() {
             return;
         }
         if adjacent_piece == Piece::Blank {
-            if this_piece == Piece::Cow || this_piece == Piece::Person {
-                return;
-            }
-            let piece_to_create = match this_piece {
-                Piece::House => Piece::Person,
-                _ => Piece::Cow,
+            // only a goal can have had a piece come into it
+            let piece_to_create = match this_piece.mover() {
+                Some(mover) => mover,
+                None => return,
             };
             for index in direction_indices {
-                reverse_moves.push((
-                    index as u8,
-                    this_index as u8,
-                    this_index as u8,
-                    piece_to_create,
-                ));
+                // as in check_move, two directions can lead through the same cells
+                let reverse_move = (index, this_index, this_index, piece_to_create);
+                if !reverse_moves.contains(&reverse_move) {
+                    reverse_moves.push(reverse_move);
+                }
             }
             return;
         }
-        if adjacent_piece == Piece::Cow && this_piece == Piece::Barn {
-            return;
-        }
-        if adjacent_piece == Piece::Person && this_piece == Piece::House {
+        // a piece next to its own goal would have gone into it
+        if adjacent_piece.goal() == Some(this_piece) {
             return;
         }
         for index in direction_indices {
-            reverse_moves.push((
-                index as u8,
-                adjacent_index as u8,
-                this_index as u8,
-                adjacent_piece,
-            ));
+            let reverse_move = (index, adjacent_index, this_index, adjacent_piece);
+            if !reverse_moves.contains(&reverse_move) {
+                reverse_moves.push(reverse_move);
+            }
         }
     }
 
-    pub fn get_possible_moves(&self) -> Vec<(Board, u8, u8, u8)> {
-        let mut moves: Vec<(Board, u8, u8, u8)> = Vec::new();
-        for i in 0..self.pieces.len() {
-            let piece = self.pieces[i];
-            if piece == Piece::Cow || piece == Piece::Person {
-                let destinations = self.get_moves_from(i as u8);
-                for (destination, puller) in destinations {
-                    let mut new_board = self.clone();
-                    let piece = new_board.pieces[i];
-                    let destination_piece = new_board.pieces[destination as usize];
-                    new_board.set_index(i, Piece::Blank);
-                    if destination_piece == Piece::Blank {
-                        new_board.set_index(destination as usize, piece);
-                    }
-                    moves.push((new_board, i as u8, destination, puller));
-                }
+    pub fn get_possible_moves(&self) -> Vec<(Board<WORDS, PLANES>, u16, u16, u16)> {
+        let mut moves: Vec<(Board<WORDS, PLANES>, u16, u16, u16)> = Vec::new();
+        for from in self.mover_mask().iter() {
+            for (to, puller) in self.get_moves_from(from) {
+                let mut new_board = *self;
+                new_board.make_move(&Move { from, to, puller });
+                moves.push((new_board, from, to, puller));
             }
         }
         moves
     }
 
-    pub fn get_possible_previous_boards(&self) -> Vec<(Board, u8, u8, u8)> {
-        let mut boards: Vec<(Board, u8, u8, u8)> = Vec::new();
-        for i in 0..self.pieces.len() {
-            let reverse_moves = self.get_reverse_moves(i as u8);
+    pub fn is_legal(&self, m: &Move) -> bool {
+        (m.from as usize) < self.cell_count()
+            && self.get_index(m.from as usize).is_mover()
+            && self.get_moves_from(m.from).contains(&(m.to, m.puller))
+    }
+
+    // the board after `m`, if it is one of this board's possible moves
+    pub fn apply(&self, m: &Move) -> Result<Board<WORDS, PLANES>, IllegalMove> {
+        if !self.is_legal(m) {
+            return Err(IllegalMove(m.clone()));
+        }
+        let mut board = *self;
+        board.make_move(m);
+        Ok(board)
+    }
+
+    // Plays `m` in place without checking it; it must be legal, as checked by is_legal or taken
+    // from get_possible_moves. A piece moved onto its goal is taken off the board.
+    pub fn make_move(&mut self, m: &Move) {
+        let piece = self.get_index(m.from as usize);
+        debug_assert!(piece.is_mover());
+        self.set_index(m.from as usize, Piece::Blank);
+        if self.get_index(m.to as usize) == Piece::Blank {
+            self.set_index(m.to as usize, piece);
+        }
+    }
+
+    // Takes back `m`, which must be the last move made on this board. Whether the piece went home is
+    // read from the cell it was moved to.
+    pub fn unmake_move(&mut self, m: &Move) {
+        let piece = self.get_index(m.to as usize);
+        let piece = match piece.mover() {
+            Some(mover) => mover,
+            None => {
+                self.set_index(m.to as usize, Piece::Blank);
+                piece
+            }
+        };
+        self.set_index(m.from as usize, piece);
+    }
+
+    pub fn get_possible_previous_boards(&self) -> Vec<(Board<WORDS, PLANES>, u16, u16, u16)> {
+        let mut boards: Vec<(Board<WORDS, PLANES>, u16, u16, u16)> = Vec::new();
+        for i in 0..self.cell_count() {
+            let reverse_moves = self.get_reverse_moves(i as u16);
             for (from, to, puller, piece) in reverse_moves {
-                let mut new_board = self.clone();
+                let mut new_board = *self;
                 new_board.set_index(from as usize, piece);
                 if to != puller {
                     new_board.set_index(to as usize, Piece::Blank);
@@ -499,199 +672,1104 @@ impl Board {
         boards
     }
 
+    // solved when no piece is left that has a goal of its kind on the board
     pub fn is_solved(&self) -> bool {
-        let mut person_count = 0;
-        let mut cow_count = 0;
-        let mut house_count = 0;
-        let mut barn_count = 0;
-        for i in 0..self.pieces.len() {
-            let piece = self.pieces[i];
-            match piece {
-                Piece::Person => person_count += 1,
-                Piece::Cow => cow_count += 1,
-                Piece::House => house_count += 1,
-                Piece::Barn => barn_count += 1,
-                _ => {}
+        Piece::HERDS
+            .iter()
+            .all(|&(mover, _)| !self.needs_home(mover) || self.piece_mask(mover).is_empty())
+    }
+
+    // Whether pieces of kind `mover` have to be home for the board to be solved. Cows can be left
+    // on a board with no barn and people on one with no house, but red and green cows always have
+    // to go home, so one with no barn of its colour can never be solved.
+    pub fn needs_home(&self, mover: Piece) -> bool {
+        match mover.goal() {
+            Some(_) if mover.is_coloured() => true,
+            Some(goal) => !self.piece_mask(goal).is_empty(),
+            None => false,
+        }
+    }
+
+    // parses the "width|height|pieces" form used in the puzzle files, with "|wrap" on the end for
+    // boards whose edges wrap
+    pub fn from_string(
+        s: &str,
+        topology: Topology,
+    ) -> Result<Board<WORDS, PLANES>, ParseBoardError> {
+        Board::parse_fields(s, topology, 1)
+    }
+
+    // `first_column` is the column of `s` within the string being parsed, for error positions
+    fn parse_fields(
+        s: &str,
+        topology: Topology,
+        first_column: usize,
+    ) -> Result<Board<WORDS, PLANES>, ParseBoardError> {
+        let error = |column: usize, kind: ParseBoardErrorKind| ParseBoardError {
+            line: None,
+            column,
+            kind,
+        };
+        let end_column = first_column + s.chars().count();
+
+        let mut fields = s.splitn(4, '|');
+        let width_field = fields.next().unwrap_or("");
+        let height_field = fields
+            .next()
+            .ok_or_else(|| error(end_column, ParseBoardErrorKind::MissingField("height")))?;
+        let pieces_field = fields
+            .next()
+            .ok_or_else(|| error(end_column, ParseBoardErrorKind::MissingField("pieces")))?;
+        let wrap_field = fields.next();
+        let height_column = first_column + width_field.chars().count() + 1;
+        let pieces_column = height_column + height_field.chars().count() + 1;
+        let wrap_column = pieces_column + pieces_field.chars().count() + 1;
+
+        let parse_size = |field: &str, name: &'static str, column: usize| match field.parse::<u8>()
+        {
+            Ok(size) if size > 0 => Ok(size),
+            _ => Err(error(
+                column,
+                ParseBoardErrorKind::InvalidSize {
+                    field: name,
+                    value: field.to_string(),
+                },
+            )),
+        };
+        let width = parse_size(width_field, "width", first_column)?;
+        let height = parse_size(height_field, "height", height_column)?;
+        let cells = width as usize * height as usize;
+        if cells > Self::MAX_CELLS {
+            return Err(error(
+                first_column,
+                ParseBoardErrorKind::TooManyCells {
+                    cells,
+                    max: Self::MAX_CELLS,
+                },
+            ));
+        }
+
+        let wraps = match wrap_field {
+            None => false,
+            Some("wrap") => true,
+            Some(field) => {
+                return Err(error(
+                    wrap_column,
+                    ParseBoardErrorKind::UnknownOption(field.to_string()),
+                ))
             }
+        };
+        if wraps && !topology.can_wrap(width, height) {
+            return Err(error(
+                wrap_column,
+                ParseBoardErrorKind::CannotWrap {
+                    topology,
+                    width,
+                    height,
+                },
+            ));
         }
-        person_count * house_count + cow_count * barn_count == 0
-    }
-
-    pub fn from_string(s: &str) -> Board {
-        // string of format "width|height|pieces"
-        let mut parts = s.split('|');
-        let width = parts.next().unwrap().parse::<u8>().unwrap();
-        let height = parts.next().unwrap().parse::<u8>().unwrap();
-        let mut board = Board::new(width, height);
-        let mut x = 0;
-        let mut y = 0;
-        for c in parts.next().unwrap().chars() {
-            let piece = match c {
-                'O' => Piece::Cow,
-                'P' => Piece::Person,
-                'H' => Piece::House,
-                'B' => Piece::Barn,
-                'E' => Piece::Empty,
-                '_' => Piece::Blank,
-                _ => panic!("Invalid character in board string"),
-            };
-            board.set(x, y, piece);
-            x += 1;
-            if x == width {
-                x = 0;
-                y += 1;
+
+        let mut board = Board::new(width, height, topology, wraps);
+        let mut count = 0;
+        for (i, c) in pieces_field.chars().enumerate() {
+            let piece = Piece::from_char(c)
+                .ok_or_else(|| error(pieces_column + i, ParseBoardErrorKind::InvalidPiece(c)))?;
+            if !Self::can_hold(piece) {
+                return Err(error(
+                    pieces_column + i,
+                    ParseBoardErrorKind::ColouredPiece(c),
+                ));
+            }
+            if i < cells {
+                board.set_index(i, piece);
             }
+            count += 1;
         }
-        board
+        if count != cells {
+            return Err(error(
+                pieces_column + count.min(cells),
+                ParseBoardErrorKind::WrongLength {
+                    expected: cells,
+                    actual: count,
+                },
+            ));
+        }
+        Ok(board)
     }
 
-    pub fn to_string(&self) -> String {
+    // compact "width|height|pieces" form used in the puzzle files, unlike the grid Display
+    #[allow(clippy::inherent_to_string_shadow_display)]
+    pub fn to_string(self) -> String {
         let mut s = String::new();
         s.push_str(&self.width.to_string());
         s.push('|');
         s.push_str(&self.height.to_string());
         s.push('|');
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let c = match self.get(x, y) {
-                    Piece::Cow => 'O',
-                    Piece::Person => 'P',
-                    Piece::House => 'H',
-                    Piece::Barn => 'B',
-                    Piece::Empty => 'E',
-                    Piece::Blank => '_',
-                };
-                s.push(c);
-            }
+        for i in 0..self.cell_count() {
+            s.push(self.get_index(i).to_char());
+        }
+        if self.wraps {
+            s.push_str("|wrap");
         }
         s
     }
 
+    // to_string tagged with the topology prefix, the form FromStr reads back
+    pub fn to_tagged_string(self) -> String {
+        format!("{}{}", self.topology.prefix(), self.to_string())
+    }
+
     fn rotate(&mut self) {
-        let mut new_pieces = vec![Piece::Blank; (self.width * self.height) as usize];
+        let mut rotated = Board::new(self.height, self.width, self.topology, self.wraps);
         for y in 0..self.height {
             for x in 0..self.width {
-                let piece = self.get(x, y);
-                new_pieces[(x * self.height + (self.height - y - 1)) as usize] = piece;
+                rotated.set(self.height - y - 1, x, self.get(x, y));
             }
         }
-        let temp = self.width;
-        self.width = self.height;
-        self.height = temp;
-        self.pieces = new_pieces;
+        *self = rotated;
     }
 
     fn flip_horizontal(&mut self) {
-        let mut new_pieces = vec![Piece::Blank; (self.width * self.height) as usize];
-        let width = self.width;
+        let mut flipped = Board::new(self.width, self.height, self.topology, self.wraps);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                flipped.set(self.width - x - 1, y, self.get(x, y));
+            }
+        }
+        *self = flipped;
+    }
+
+    fn flip_vertical(&mut self) {
+        let mut flipped = Board::new(self.width, self.height, self.topology, self.wraps);
         for y in 0..self.height {
             for x in 0..self.width {
-                let piece = self.get(x, y);
-                new_pieces[(y * width + (width - x - 1)) as usize] = piece;
+                flipped.set(x, self.height - y - 1, self.get(x, y));
             }
         }
-        self.pieces = new_pieces;
+        *self = flipped;
     }
 
     fn switch_cow_person(&mut self) {
-        for i in 0..self.pieces.len() {
-            let piece = self.pieces[i];
+        for i in 0..self.cell_count() {
+            let piece = self.get_index(i);
             match piece {
-                Piece::Cow => self.pieces[i] = Piece::Person,
-                Piece::Person => self.pieces[i] = Piece::Cow,
-                Piece::House => self.pieces[i] = Piece::Barn,
-                Piece::Barn => self.pieces[i] = Piece::House,
+                Piece::Cow => self.set_index(i, Piece::Person),
+                Piece::Person => self.set_index(i, Piece::Cow),
+                Piece::House => self.set_index(i, Piece::Barn),
+                Piece::Barn => self.set_index(i, Piece::House),
                 _ => {}
             }
         }
     }
 
-    pub fn get_symmetric_variants(&self) -> Vec<Board> {
+    pub fn get_symmetric_variants(&self) -> Vec<Board<WORDS, PLANES>> {
         let mut variants = Vec::new();
-        let mut board = self.clone();
-        variants.push(board.clone());
-        for _ in 0..3 {
-            board.rotate();
-            variants.push(board.clone());
-        }
-        board.flip_horizontal();
-        variants.push(board.clone());
-        for _ in 0..3 {
-            board.rotate();
-            variants.push(board.clone());
+        let mut board = *self;
+        variants.push(board);
+        match self.topology {
+            Topology::Rect => {
+                for _ in 0..3 {
+                    board.rotate();
+                    variants.push(board);
+                }
+                board.flip_horizontal();
+                variants.push(board);
+                for _ in 0..3 {
+                    board.rotate();
+                    variants.push(board);
+                }
+            }
+            Topology::Hex => {
+                // Only moves that keep the odd rows shifted right give a hex board back. Mirroring
+                // top to bottom does that for an odd number of rows, and for an even number a half
+                // turn (mirroring both ways) does, since the last row is then odd.
+                board.flip_vertical();
+                if self.height.is_multiple_of(2) {
+                    board.flip_horizontal();
+                }
+                variants.push(board);
+            }
+            Topology::Triangle => {
+                // A mirror image of the grid is a triangle grid again only if every triangle lands
+                // on one pointing the way its image does. Mirroring left to right keeps the way
+                // triangles point, so needs an odd width; top to bottom turns them over, so needs
+                // an even height, and a half turn, both at once, an odd width plus height.
+                let odd_width = !self.width.is_multiple_of(2);
+                let even_height = self.height.is_multiple_of(2);
+                if odd_width {
+                    board.flip_horizontal();
+                    variants.push(board);
+                    board = *self;
+                }
+                if even_height {
+                    board.flip_vertical();
+                    variants.push(board);
+                    board = *self;
+                }
+                if odd_width == even_height {
+                    board.flip_horizontal();
+                    board.flip_vertical();
+                    variants.push(board);
+                }
+            }
         }
         let count = variants.len();
         for i in 0..count {
-            let mut board = variants[i].clone();
+            let mut board = variants[i];
             board.switch_cow_person();
             variants.push(board);
         }
         variants
     }
 
-    pub fn count_piece(&self, piece: Piece) -> u8 {
-        let mut count = 0;
-        for i in 0..(self.width * self.height) {
-            if self.pieces[i as usize] == piece {
-                count += 1;
+    // the same board for every member of a symmetry class, so symmetric boards can share one entry
+    pub fn canonical(&self) -> Board<WORDS, PLANES> {
+        self.get_symmetric_variants().into_iter().min().unwrap()
+    }
+
+    // key a search stores this board under, shared with its symmetric variants if use_symmetry is set
+    pub fn search_key(&self, use_symmetry: bool) -> Board<WORDS, PLANES> {
+        if use_symmetry {
+            self.canonical()
+        } else {
+            *self
+        }
+    }
+
+    pub fn count_piece(&self, piece: Piece) -> usize {
+        self.piece_mask(piece).len()
+    }
+
+    // the cells holding a piece that moves, of any kind
+    pub fn mover_mask(&self) -> CellSet<WORDS> {
+        Piece::HERDS
+            .iter()
+            .fold(CellSet::new(), |mask, &(mover, _)| {
+                mask | self.piece_mask(mover)
+            })
+    }
+
+    // whether any red or green piece is on the board, which only a coloured board can hold
+    pub fn has_coloured_pieces(&self) -> bool {
+        Piece::ALL
+            .iter()
+            .any(|&piece| piece.is_coloured() && self.count_piece(piece) > 0)
+    }
+
+    // cows of every colour, which is what limits on the number of cows count
+    pub fn count_cows(&self) -> usize {
+        Piece::HERDS
+            .iter()
+            .filter(|(mover, _)| mover.is_cow())
+            .map(|&(mover, _)| self.count_piece(mover))
+            .sum()
+    }
+}
+
+pub struct Ray<'a, const WORDS: usize = 1, const PLANES: usize = PLAIN_PLANES> {
+    board: &'a Board<WORDS, PLANES>,
+    start: u16,
+    index: u16,
+    direction: Direction,
+}
+
+impl<const WORDS: usize, const PLANES: usize> Iterator for Ray<'_, WORDS, PLANES> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        let next = self.board.neighbour(self.index, self.direction)?;
+        // Each direction steps every cell to a different one, so a ray on a board that wraps runs
+        // in a loop through its start. A piece that got that far has nothing to stop it and slides
+        // round forever, which is no move.
+        if next == self.start {
+            return None;
+        }
+        self.index = next;
+        Some(next)
+    }
+}
+
+// reads the topology names used on the command line, which are the same as the directory names
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Topology, String> {
+        Topology::ALL
+            .into_iter()
+            .find(|topology| topology.directory() == s)
+            .ok_or_else(|| format!("unknown topology '{}'", s))
+    }
+}
+
+// Reads a board tagged with its topology as in the combined puzzle lists, e.g. "H4|3|B__P____OP_O".
+// Untagged strings are read as rect boards, like the puzzle files from before hex mode.
+impl<const WORDS: usize, const PLANES: usize> FromStr for Board<WORDS, PLANES> {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Board<WORDS, PLANES>, ParseBoardError> {
+        match s.chars().next() {
+            Some(prefix) if prefix.is_ascii_alphabetic() => {
+                let topology = Topology::from_prefix(prefix).ok_or(ParseBoardError {
+                    line: None,
+                    column: 1,
+                    kind: ParseBoardErrorKind::UnknownTopology(prefix),
+                })?;
+                Board::parse_fields(&s[1..], topology, 2)
+            }
+            _ => Board::parse_fields(s, Topology::Rect, 1),
+        }
+    }
+}
+
+// a move that isn't one of the board's possible moves
+#[derive(Clone, Debug)]
+pub struct IllegalMove(pub Move);
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let IllegalMove(m) = self;
+        write!(
+            f,
+            "can't move from {} to {} pulled by {}",
+            m.from, m.to, m.puller
+        )
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseBoardErrorKind {
+    MissingField(&'static str),
+    InvalidSize {
+        field: &'static str,
+        value: String,
+    },
+    TooManyCells {
+        cells: usize,
+        max: usize,
+    },
+    InvalidPiece(char),
+    // a coloured piece on a board read as a kind without room for them
+    ColouredPiece(char),
+    WrongLength {
+        expected: usize,
+        actual: usize,
+    },
+    UnknownTopology(char),
+    UnknownOption(String),
+    CannotWrap {
+        topology: Topology,
+        width: u8,
+        height: u8,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseBoardError {
+    // set by callers reading boards out of a file
+    pub line: Option<usize>,
+    // 1-based character position in the board string
+    pub column: usize,
+    pub kind: ParseBoardErrorKind,
+}
+
+impl ParseBoardError {
+    pub fn with_line(self, line: usize) -> ParseBoardError {
+        ParseBoardError {
+            line: Some(line),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}, ", line)?;
+        }
+        write!(f, "column {}: ", self.column)?;
+        match &self.kind {
+            ParseBoardErrorKind::MissingField(field) => write!(f, "missing {} field", field),
+            ParseBoardErrorKind::InvalidSize { field, value } => {
+                write!(
+                    f,
+                    "{} must be a number from 1 to 255, found '{}'",
+                    field, value
+                )
+            }
+            ParseBoardErrorKind::TooManyCells { cells, max } => {
+                write!(
+                    f,
+                    "board has {} cells, at most {} are supported",
+                    cells, max
+                )
+            }
+            ParseBoardErrorKind::InvalidPiece(c) => write!(f, "unknown piece '{}'", c),
+            ParseBoardErrorKind::ColouredPiece(c) => {
+                write!(f, "coloured piece '{}' needs a coloured board", c)
+            }
+            ParseBoardErrorKind::WrongLength { expected, actual } => {
+                write!(f, "expected {} pieces, found {}", expected, actual)
+            }
+            ParseBoardErrorKind::UnknownTopology(c) => write!(f, "unknown topology '{}'", c),
+            ParseBoardErrorKind::UnknownOption(option) => {
+                write!(f, "unknown board option '{}', expected 'wrap'", option)
+            }
+            ParseBoardErrorKind::CannotWrap {
+                topology,
+                width,
+                height,
+            } => {
+                let needs = match topology {
+                    Topology::Hex => "an even height",
+                    _ => "an even width and height",
+                };
+                write!(
+                    f,
+                    "a {}x{} {} board can't wrap, that needs {}",
+                    width,
+                    height,
+                    topology.directory(),
+                    needs
+                )
             }
         }
-        count
     }
 }
 
+impl std::error::Error for ParseBoardError {}
+
+// Two directions can lead through the same cells, so the same move may be found twice: on triangles
+// the first cell is shared, and on a board that wraps and is two rows high, going up and down can
+// follow the same line.
 fn check_move(
     piece: Piece,
     new_piece: Piece,
-    moves: &mut Vec<(u8, u8)>,
-    space: u8,
-    prev_space: u8,
+    moves: &mut Vec<(u16, u16)>,
+    space: u16,
+    prev_space: u16,
     has_prev_space: bool,
 ) -> bool {
     if new_piece == Piece::Empty {
         return true;
     }
-    if new_piece == Piece::Person
-        || new_piece == Piece::Cow
-        || (new_piece == Piece::House && piece == Piece::Cow)
-        || (new_piece == Piece::Barn && piece == Piece::Person)
-    {
-        if has_prev_space {
-            moves.push((prev_space, space));
+    if piece.goal() == Some(new_piece) {
+        if !moves.contains(&(space, space)) {
+            moves.push((space, space));
         }
         return true;
     }
-    if (new_piece == Piece::House && piece == Piece::Person)
-        || (new_piece == Piece::Barn && piece == Piece::Cow)
-    {
-        moves.push((space, space));
+    // other pieces and goals of any other kind pull it up short
+    if new_piece.is_mover() || new_piece.is_goal() {
+        if has_prev_space && !moves.contains(&(prev_space, space)) {
+            moves.push((prev_space, space));
+        }
         return true;
     }
-    return false;
+    false
 }
 
-impl fmt::Display for Board {
+impl<const WORDS: usize, const PLANES: usize> fmt::Display for Board<WORDS, PLANES> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // cells outside the board are left blank
+        let cell_char = |x: u8, y: u8| match self.get(x, y) {
+            Piece::Outside => ' ',
+            piece => piece.to_char(),
+        };
+        if self.topology == Topology::Triangle {
+            // each piece between the sides of its triangle, "/O\" pointing up and "\O/" down,
+            // neighbours sharing a side, which is left out when neither of them is on the board
+            let inside = |x: u8, y: u8| x < self.width && self.get(x, y) != Piece::Outside;
+            for y in 0..self.height {
+                for x in 0..=self.width {
+                    let up = (x as usize + y as usize).is_multiple_of(2);
+                    let side = if up { '/' } else { '\\' };
+                    let has_side = inside(x, y) || (x > 0 && inside(x - 1, y));
+                    write!(f, "{}", if has_side { side } else { ' ' })?;
+                    if x < self.width {
+                        write!(f, "{}", cell_char(x, y))?;
+                    }
+                }
+                writeln!(f)?;
+            }
+            return Ok(());
+        }
         for y in 0..self.height {
             for x in 0..self.width {
-                if HEXAGONAL_MODE && y % 2 == 1 {
+                if self.topology == Topology::Hex && y % 2 == 1 {
                     write!(f, " ")?;
                 }
-                let c = match self.get(x, y) {
-                    Piece::Cow => 'O',
-                    Piece::Person => 'P',
-                    Piece::House => 'H',
-                    Piece::Barn => 'B',
-                    Piece::Empty => 'E',
-                    Piece::Blank => '_',
-                };
-                write!(f, "{}", c)?;
-                if y % 2 == 0 || !HEXAGONAL_MODE {
+                write!(f, "{}", cell_char(x, y))?;
+                if y % 2 == 0 || self.topology != Topology::Hex {
                     write!(f, " ")?;
                 }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::deadlock::is_dead;
+    use crate::solve::solve_shortest;
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+    fn opposite(direction: Direction) -> Direction {
+        match direction {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+
+    fn blank(width: u8, height: u8, topology: Topology) -> Board {
+        Board::new(width, height, topology, false)
+    }
+
+    // neighbours in Left, Right, UpLeft, UpRight, DownLeft, DownRight order, on hex and triangle
+    // boards
+    fn diagonal_neighbours<const WORDS: usize>(
+        board: &Board<WORDS>,
+        index: u16,
+    ) -> Vec<Option<u16>> {
+        board
+            .topology
+            .directions()
+            .iter()
+            .map(|&direction| board.neighbour(index, direction))
+            .collect()
+    }
+
+    #[test]
+    fn hex_neighbours_on_even_rows() {
+        let board = blank(4, 4, Topology::Hex);
+        // left edge, row 2
+        assert_eq!(
+            diagonal_neighbours(&board, 8),
+            [None, Some(9), None, Some(4), None, Some(12)]
+        );
+        // right edge, row 2
+        assert_eq!(
+            diagonal_neighbours(&board, 11),
+            [Some(10), None, Some(6), Some(7), Some(14), Some(15)]
+        );
+        // top row
+        assert_eq!(
+            diagonal_neighbours(&board, 1),
+            [Some(0), Some(2), None, None, Some(4), Some(5)]
+        );
+    }
+
+    #[test]
+    fn hex_neighbours_on_odd_rows() {
+        let board = blank(4, 4, Topology::Hex);
+        // left edge, row 1
+        assert_eq!(
+            diagonal_neighbours(&board, 4),
+            [None, Some(5), Some(0), Some(1), Some(8), Some(9)]
+        );
+        // right edge, row 1
+        assert_eq!(
+            diagonal_neighbours(&board, 7),
+            [Some(6), None, Some(3), None, Some(11), None]
+        );
+        // bottom row
+        assert_eq!(
+            diagonal_neighbours(&board, 13),
+            [Some(12), Some(14), Some(9), Some(10), None, None]
+        );
+    }
+
+    // An up triangle's diagonals cross its slanted sides into the row and its flat side down; a
+    // down triangle's cross into the row and up.
+    #[test]
+    fn triangle_neighbours_zigzag() {
+        let board = blank(4, 4, Topology::Triangle);
+        // up, row 1
+        assert_eq!(
+            diagonal_neighbours(&board, 5),
+            [Some(4), Some(6), Some(4), Some(6), Some(9), Some(9)]
+        );
+        // down, row 1
+        assert_eq!(
+            diagonal_neighbours(&board, 6),
+            [Some(5), Some(7), Some(2), Some(2), Some(5), Some(7)]
+        );
+        // up, top left corner
+        assert_eq!(
+            diagonal_neighbours(&board, 0),
+            [None, Some(1), None, Some(1), Some(4), Some(4)]
+        );
+        // down, top row
+        assert_eq!(
+            diagonal_neighbours(&board, 1),
+            [Some(0), Some(2), None, None, Some(0), Some(2)]
+        );
+        for index in [0, 5, 6] {
+            assert_eq!(board.neighbour(index, Direction::Up), None);
+            assert_eq!(board.neighbour(index, Direction::Down), None);
+        }
+    }
+
+    // across a wrapped edge, a down triangle in the top row meets the up one below it in the bottom
+    // row, flat side to flat side
+    #[test]
+    fn triangle_neighbours_wrap() {
+        let board =
+            Board::<1>::from_string(&format!("4|4|{}|wrap", "_".repeat(16)), Topology::Triangle)
+                .unwrap();
+        assert_eq!(
+            diagonal_neighbours(&board, 0),
+            [Some(3), Some(1), Some(3), Some(1), Some(4), Some(4)]
+        );
+        assert_eq!(
+            diagonal_neighbours(&board, 1),
+            [Some(0), Some(2), Some(13), Some(13), Some(0), Some(2)]
+        );
+        assert_eq!(
+            diagonal_neighbours(&board, 15),
+            [Some(14), Some(12), Some(14), Some(12), Some(3), Some(3)]
+        );
+        assert_eq!(board.neighbour(1, Direction::Up), None);
+        assert_eq!(board.neighbour(15, Direction::Down), None);
+    }
+
+    #[test]
+    fn rect_neighbours_stop_at_the_edges() {
+        let board = blank(3, 2, Topology::Rect);
+        let neighbours = |index| {
+            Topology::Rect
+                .directions()
+                .iter()
+                .map(|&direction| board.neighbour(index, direction))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(neighbours(0), [None, Some(1), None, Some(3)]);
+        assert_eq!(neighbours(5), [Some(4), None, Some(2), None]);
+    }
+
+    // A direction a topology doesn't have leads nowhere, even from the middle of a board that
+    // wraps: rect boards have no diagonals, hex and triangle ones no straight up or down.
+    #[test]
+    fn missing_directions_have_no_neighbours() {
+        let all = [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+            Direction::UpLeft,
+            Direction::UpRight,
+            Direction::DownLeft,
+            Direction::DownRight,
+        ];
+        for topology in Topology::ALL {
+            let board =
+                Board::<1>::from_string(&format!("4|4|{}|wrap", "_".repeat(16)), topology).unwrap();
+            for direction in all {
+                let has = topology.directions().contains(&direction);
+                for index in 0..16 {
+                    assert_eq!(
+                        board.neighbour(index, direction).is_some(),
+                        has,
+                        "{:?} {:?} from {}",
+                        topology,
+                        direction,
+                        index
+                    );
+                }
+            }
+        }
+    }
+
+    // the reverse move generator walks back along the rays moves take
+    #[test]
+    fn opposite_directions_invert_each_other() {
+        let mut boards = vec![
+            blank(5, 4, Topology::Rect),
+            blank(5, 4, Topology::Hex),
+            blank(5, 5, Topology::Hex),
+            blank(5, 4, Topology::Triangle),
+            blank(4, 3, Topology::Triangle),
+        ];
+        // and with the edges wrapping
+        for (width, height, topology) in [
+            (5, 4, Topology::Rect),
+            (5, 4, Topology::Hex),
+            (4, 4, Topology::Triangle),
+        ] {
+            boards.push(Board::new(width, height, topology, true));
+        }
+        for board in boards {
+            for index in 0..board.cell_count() as u16 {
+                for &direction in board.topology.directions() {
+                    if let Some(next) = board.neighbour(index, direction) {
+                        assert_eq!(
+                            board.neighbour(next, opposite(direction)),
+                            Some(index),
+                            "{:?} from {} on {}",
+                            direction,
+                            index,
+                            board.to_tagged_string()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // random boards of the given size with a barn, a house and pieces for them on half the cells
+    fn random_boards(width: u8, height: u8, topology: Topology, count: usize) -> Vec<Board> {
+        let mut rng = StdRng::seed_from_u64(u64::from(width) << 8 | u64::from(height));
+        let pieces = [
+            Piece::Barn,
+            Piece::House,
+            Piece::Cow,
+            Piece::Person,
+            Piece::Empty,
+            Piece::Cow,
+            Piece::Person,
+        ];
+        (0..count)
+            .map(|_| {
+                let mut board = blank(width, height, topology);
+                let mut cells: Vec<usize> = (0..board.cell_count()).collect();
+                cells.shuffle(&mut rng);
+                for (&i, &piece) in cells
+                    .iter()
+                    .zip(pieces.iter().cycle())
+                    .take(cells.len() / 2)
+                {
+                    board.set_index(i, piece);
+                }
+                board
+            })
+            .collect()
+    }
+
+    fn check_variants_solve_alike(topology: Topology, sizes: &[(u8, u8)]) {
+        let mut solvable = 0;
+        for &(width, height) in sizes {
+            for board in random_boards(width, height, topology, 12) {
+                let solution = solve_shortest(board, false);
+                solvable += solution.can_be_solved as usize;
+                let variants = board.get_symmetric_variants();
+                assert!(
+                    variants.len() > 2,
+                    "{} has no mirror images",
+                    board.to_tagged_string()
+                );
+                for variant in variants {
+                    let variant_solution = solve_shortest(variant, false);
+                    assert_eq!(
+                        (variant_solution.can_be_solved, variant_solution.move_count),
+                        (solution.can_be_solved, solution.move_count),
+                        "{} and its variant {}",
+                        board.to_tagged_string(),
+                        variant.to_tagged_string()
+                    );
+                }
+            }
+        }
+        assert!(solvable > 0);
+    }
+
+    // both ways of mirroring a hex board, for odd and even numbers of rows
+    #[test]
+    fn hex_variants_solve_alike() {
+        check_variants_solve_alike(Topology::Hex, &[(3, 3), (4, 2), (4, 3), (3, 4)]);
+    }
+
+    // one size for each combination of odd and even width and height
+    #[test]
+    fn triangle_variants_solve_alike() {
+        check_variants_solve_alike(Topology::Triangle, &[(3, 2), (3, 3), (4, 2), (4, 3)]);
+    }
+
+    // Every (board, recorded move count) row of each puzzle file in a corpus directory, a file at a
+    // time.
+    pub(crate) fn corpus_files(directory: &str) -> Vec<Vec<(String, String)>> {
+        let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), directory);
+        let mut files: Vec<_> = std::fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+            .iter()
+            .map(|file| {
+                let contents = std::fs::read_to_string(file).unwrap();
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|line| {
+                        let fields: Vec<&str> = line.split('\t').collect();
+                        (fields[0].to_string(), fields[2].to_string())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // A few rows spread across each puzzle file in a corpus directory, so the hard boards at the
+    // end are covered too.
+    pub(crate) fn corpus_rows(directory: &str) -> Vec<(String, String)> {
+        const SAMPLES_PER_FILE: usize = 4;
+        let mut rows = Vec::new();
+        for file_rows in corpus_files(directory) {
+            let step = (file_rows.len() / SAMPLES_PER_FILE).max(1);
+            rows.extend(
+                file_rows
+                    .iter()
+                    .step_by(step)
+                    .chain(file_rows.last())
+                    .cloned(),
+            );
+        }
+        rows
+    }
+
+    fn parse_error<const WORDS: usize>(s: &str) -> (usize, ParseBoardErrorKind) {
+        match s.parse::<Board<WORDS>>() {
+            Ok(_) => panic!("{} parsed", s),
+            Err(error) => (error.column, error.kind),
+        }
+    }
+
+    #[test]
+    fn corpus_boards_round_trip() {
+        for (directory, topology) in [("rect", Topology::Rect), ("hex", Topology::Hex)] {
+            for (s, _) in corpus_rows(directory) {
+                let board = Board::<1>::from_string(&s, topology).unwrap();
+                assert_eq!(board.to_string(), s);
+                assert!(board.to_tagged_string().parse::<Board>().unwrap() == board);
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_and_shaped_boards_round_trip() {
+        for (s, topology) in [
+            ("4|3|B_O.__P_.__H|wrap", Topology::Rect),
+            ("4|4|.B__O_P__E____H.|wrap", Topology::Hex),
+            ("4|2|.BO__P..", Topology::Triangle),
+        ] {
+            let board = Board::<1>::from_string(s, topology).unwrap();
+            assert_eq!(board.to_string(), s);
+            assert_eq!(board.wraps, s.ends_with("|wrap"));
+            assert_eq!(board.get_index(s.find('.').unwrap() - 4), Piece::Outside);
+            assert!(board.to_tagged_string().parse::<Board>().unwrap() == board);
+        }
+    }
+
+    #[test]
+    fn parse_errors_point_at_their_column() {
+        assert_eq!(
+            parse_error::<1>("3"),
+            (2, ParseBoardErrorKind::MissingField("height"))
+        );
+        assert_eq!(
+            parse_error::<1>("3|2"),
+            (4, ParseBoardErrorKind::MissingField("pieces"))
+        );
+        assert_eq!(
+            parse_error::<1>("0|2|"),
+            (
+                1,
+                ParseBoardErrorKind::InvalidSize {
+                    field: "width",
+                    value: "0".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            parse_error::<1>("3|x|______"),
+            (
+                3,
+                ParseBoardErrorKind::InvalidSize {
+                    field: "height",
+                    value: "x".to_string()
+                }
+            )
+        );
+        assert_eq!(
+            parse_error::<1>("9|8|"),
+            (1, ParseBoardErrorKind::TooManyCells { cells: 72, max: 64 })
+        );
+        assert_eq!(
+            parse_error::<1>("3|2|__X___"),
+            (7, ParseBoardErrorKind::InvalidPiece('X'))
+        );
+        assert_eq!(
+            parse_error::<1>("3|2|_____"),
+            (
+                10,
+                ParseBoardErrorKind::WrongLength {
+                    expected: 6,
+                    actual: 5
+                }
+            )
+        );
+        assert_eq!(
+            parse_error::<1>("3|2|_______"),
+            (
+                11,
+                ParseBoardErrorKind::WrongLength {
+                    expected: 6,
+                    actual: 7
+                }
+            )
+        );
+        assert_eq!(
+            parse_error::<1>("X3|2|______"),
+            (1, ParseBoardErrorKind::UnknownTopology('X'))
+        );
+        assert_eq!(
+            parse_error::<1>("3|2|______|wrapped"),
+            (
+                12,
+                ParseBoardErrorKind::UnknownOption("wrapped".to_string())
+            )
+        );
+        assert_eq!(
+            parse_error::<1>("H3|3|_________|wrap"),
+            (
+                16,
+                ParseBoardErrorKind::CannotWrap {
+                    topology: Topology::Hex,
+                    width: 3,
+                    height: 3
+                }
+            )
+        );
+        // columns count the topology prefix
+        assert_eq!(
+            parse_error::<1>("H3|2|__X___"),
+            (8, ParseBoardErrorKind::InvalidPiece('X'))
+        );
+    }
+
+    // coloured pieces need the fourth plane, which the default Board leaves out to stay small
+    #[test]
+    fn coloured_pieces_need_a_coloured_board() {
+        assert_eq!(std::mem::size_of::<Board>(), 32);
+        assert_eq!(
+            parse_error::<1>("3|2|R_r___"),
+            (5, ParseBoardErrorKind::ColouredPiece('R'))
+        );
+        assert_eq!(
+            parse_error::<1>("3|2|B_O_g_"),
+            (9, ParseBoardErrorKind::ColouredPiece('g'))
+        );
+        let s = "3|3|RrG_g_BOP";
+        let board = ColouredBoard::from_string(s, Topology::Rect).unwrap();
+        assert_eq!(board.to_string(), s);
+        assert!(board.has_coloured_pieces());
+        assert_eq!(board.count_cows(), 3);
+        assert!(!Board::<1>::from_string("3|3|B_O_P____", Topology::Rect)
+            .unwrap()
+            .has_coloured_pieces());
+    }
+
+    // A coloured cow only goes into a barn of its own colour; one of another colour stops it like a
+    // house would. Unlike a plain cow, one left on a board with no barn of its colour isn't solved.
+    #[test]
+    fn coloured_cows_only_go_into_their_own_barns() {
+        let moves = |s| {
+            let board = ColouredBoard::from_string(s, Topology::Rect).unwrap();
+            board
+                .get_possible_moves()
+                .into_iter()
+                .map(|(next_board, from, to, puller)| (next_board.to_string(), from, to, puller))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(moves("4|1|R_r_"), [("4|1|R___".to_string(), 2, 0, 0)]);
+        assert_eq!(moves("4|1|G_r_"), [("4|1|Gr__".to_string(), 2, 1, 0)]);
+        assert_eq!(moves("3|1|Gr_"), []);
+
+        let solved = |s| {
+            ColouredBoard::from_string(s, Topology::Rect)
+                .unwrap()
+                .is_solved()
+        };
+        assert!(!solved("3|1|G_r"));
+        assert!(!solved("3|1|B_r"));
+        assert!(!solved("3|1|H_r"));
+        assert!(solved("3|1|G_O"));
+        assert!(solved("3|1|G_P"));
+        let stranded = ColouredBoard::from_string("3|2|G_rP__", Topology::Rect).unwrap();
+        assert!(is_dead(&stranded));
+    }
+
+    #[test]
+    fn large_boards_round_trip() {
+        let s = format!("20|20|B{}_{}|wrap", "O".repeat(18), "_P".repeat(190));
+        let board: LargeBoard = LargeBoard::from_string(&s, Topology::Rect).unwrap();
+        assert_eq!(board.cell_count(), 400);
+        assert_eq!(board.get_index(399), Piece::Person);
+        assert_eq!(board.to_string(), s);
+        assert!(board.to_tagged_string().parse::<LargeBoard>().unwrap() == board);
+    }
+
+    #[test]
+    fn too_many_cells_for_the_board_type() {
+        assert!(
+            Board::<1>::from_string(&format!("8|8|{}", "_".repeat(64)), Topology::Rect).is_ok()
+        );
+        assert_eq!(
+            parse_error::<1>("13|5|"),
+            (1, ParseBoardErrorKind::TooManyCells { cells: 65, max: 64 })
+        );
+        assert!(
+            LargeBoard::from_string(&format!("32|32|{}", "_".repeat(1024)), Topology::Rect).is_ok()
+        );
+        assert_eq!(
+            parse_error::<LARGE_BOARD_WORDS>("41|25|"),
+            (
+                1,
+                ParseBoardErrorKind::TooManyCells {
+                    cells: 1025,
+                    max: 1024
+                }
+            )
+        );
+    }
+
+    // indices past 255 on a 20x20 board, where cell 300 starts row 15
+    #[test]
+    fn large_board_neighbours_and_rays() {
+        let board: LargeBoard = Board::new(20, 20, Topology::Rect, false);
+        assert_eq!(board.neighbour(300, Direction::Left), None);
+        assert_eq!(board.neighbour(300, Direction::Right), Some(301));
+        assert_eq!(board.neighbour(300, Direction::Up), Some(280));
+        assert_eq!(board.neighbour(300, Direction::Down), Some(320));
+        assert_eq!(
+            board.ray(300, Direction::Right).collect::<Vec<_>>(),
+            (301..320).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            board.ray(300, Direction::Down).collect::<Vec<_>>(),
+            [320, 340, 360, 380]
+        );
+
+        let wrapping: LargeBoard = Board::new(20, 20, Topology::Rect, true);
+        assert_eq!(wrapping.neighbour(300, Direction::Left), Some(319));
+        assert_eq!(wrapping.neighbour(390, Direction::Down), Some(10));
+        assert_eq!(wrapping.ray(300, Direction::Down).count(), 19);
+
+        let hex: LargeBoard = Board::new(20, 20, Topology::Hex, false);
+        assert_eq!(
+            diagonal_neighbours(&hex, 300),
+            [None, Some(301), Some(280), Some(281), Some(320), Some(321)]
+        );
+    }
+}
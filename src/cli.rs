@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+use std::thread;
+
+use solver::{
+    bidirectional::solve_bidirectional,
+    board::{Board, ColouredBoard, LargeBoard, LargeColouredBoard, ParseBoardError, Topology},
+    combine::{combine_puzzles, CombineOptions},
+    generate::{generate_puzzles, GenerateOptions},
+    heuristic::{line_distance, movers_not_home, Heuristic},
+    hint::{hint, Hint},
+    reverse_solver::reverse_solve_deepest,
+    solve::{
+        count_optimal_solutions, optimal_solutions, solve, solve_astar, solve_ida_star,
+        solve_shortest, verify_solution, Move, Solution,
+    },
+    tablebase::{build_tablebase, Tablebase},
+};
+
+pub const USAGE: &str = "usage:
+  solver solve <board> [--topology <t>] [--method bfs|astar|ida|bidirectional|dfs]
+                       [--heuristic movers|lines] [--symmetry]
+  solver generate --width <n> --height <n> [--topology <t>] [--max-barns <n>]
+                  [--max-houses <n>] [--max-empty <n>] [--layouts <n>] [--combinations <n>]
+                  [--max-cows <n>] [--max-people <n>] [--max-people-without-houses <n>]
+                  [--per-search <n>] [--elegant-in-search] [--max-solutions <n>] [--intended]
+                  [--wrap] [--outside <cells>] [--output <dir>]
+  solver reverse-solve <board> --cows <n> --people <n> [--topology <t>] [--symmetry]
+                       [--count <n>] [--elegant]
+  solver solutions <board> [--topology <t>] [--list <n>]
+  solver hint <board> [--topology <t>] [--tablebase <file>]
+  solver tablebase <layout> --cows <n> --people <n> --output <file> [--topology <t>]
+  solver combine [--rect-dir <dir>] [--hex-dir <dir>] [--per-bucket <n>]
+  solver verify [<file>...] [--topology <t>] [--every <n>]
+  solver check <board> --moves <moves> [--topology <t>]
+
+Topologies are rect, hex and triangle. Boards are written width|height|pieces, optionally tagged
+with their topology (R, H or T) as in H4|3|B__P____OP_O. Untagged boards are rect unless
+--topology says otherwise. Boards whose edges wrap end in |wrap, as in 3|3|B__P_OP_O|wrap; hex
+ones need an even height and triangle ones an even width and height. Cells written . are outside
+the board, for boards that aren't rectangles; generate leaves out the comma separated --outside
+cells. Red and green cows, r and g, only go into barns of their own colour, R and G, and unlike
+other pieces always have to get home; hint and tablebase don't take boards with them. Boards can
+have up to 1024 cells, e.g. 32x32; hint and tablebase take boards of up to 64. Moves are written
+from,to,puller cell indices, separated by spaces.";
+
+// command line arguments split into positional values, --name value options and --switches
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    fn parse(
+        args: &[String],
+        option_names: &[&str],
+        switch_names: &[&str],
+    ) -> Result<Args, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: HashMap::new(),
+            switches: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if switch_names.contains(&name) => {
+                    parsed.switches.push(name.to_string())
+                }
+                Some(name) if option_names.contains(&name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{} needs a value", name))?;
+                    parsed.options.insert(name.to_string(), value.clone());
+                }
+                Some(name) => return Err(format!("unknown option --{}", name)),
+                None => parsed.positional.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.options.get(name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value '{}' for --{}", value, name)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        Ok(self.get(name)?.unwrap_or(default))
+    }
+
+    fn require<T: FromStr>(&self, name: &str) -> Result<T, String> {
+        self.get(name)?
+            .ok_or_else(|| format!("--{} is required", name))
+    }
+
+    fn has(&self, switch: &str) -> bool {
+        self.switches.iter().any(|s| s == switch)
+    }
+
+    // the single positional board argument, read with --topology if given
+    fn board<const WORDS: usize, const PLANES: usize>(
+        &self,
+    ) -> Result<Board<WORDS, PLANES>, String> {
+        let text = self.board_text()?;
+        parse_board(text, self.get("topology")?)
+            .map_err(|error| format!("invalid board '{}': {}", text, error))
+    }
+
+    // the board argument, read into the smallest kind of board it fits in
+    fn any_board(&self) -> Result<AnyBoard, String> {
+        let text = self.board_text()?;
+        AnyBoard::parse(text, self.get("topology")?)
+            .map_err(|error| format!("invalid board '{}': {}", text, error))
+    }
+
+    fn board_text(&self) -> Result<&str, String> {
+        match self.positional.as_slice() {
+            [text] => Ok(text),
+            [] => Err("missing board".to_string()),
+            _ => Err("expected a single board".to_string()),
+        }
+    }
+}
+
+// a board in `topology`, or in the one its tag gives (rect if untagged) when that is None
+fn parse_board<const WORDS: usize, const PLANES: usize>(
+    text: &str,
+    topology: Option<Topology>,
+) -> Result<Board<WORDS, PLANES>, ParseBoardError> {
+    match topology {
+        Some(topology) => Board::from_string(text, topology),
+        None => text.parse(),
+    }
+}
+
+// A board of whichever kind is smallest for it: a LargeBoard only if it doesn't fit in a Board,
+// and a coloured one only if it has red or green pieces.
+enum AnyBoard {
+    Small(Board),
+    Large(Box<LargeBoard>),
+    Coloured(ColouredBoard),
+    LargeColoured(Box<LargeColouredBoard>),
+}
+
+impl AnyBoard {
+    fn parse(text: &str, topology: Option<Topology>) -> Result<AnyBoard, ParseBoardError> {
+        let board: LargeColouredBoard = parse_board(text, topology)?;
+        let small = board.cell_count() <= Board::<1>::MAX_CELLS;
+        Ok(match (board.has_coloured_pieces(), small) {
+            (false, true) => AnyBoard::Small(parse_board(text, topology)?),
+            (false, false) => AnyBoard::Large(Box::new(parse_board(text, topology)?)),
+            (true, true) => AnyBoard::Coloured(parse_board(text, topology)?),
+            (true, false) => AnyBoard::LargeColoured(Box::new(board)),
+        })
+    }
+}
+
+// Evaluates `body` with `board` bound to the board in an AnyBoard, unboxed, whatever its kind. A
+// macro, as the body is compiled once for each kind of board.
+macro_rules! with_board {
+    ($any:expr, |$board:ident| $body:expr) => {
+        match $any {
+            AnyBoard::Small($board) => $body,
+            AnyBoard::Large($board) => {
+                let $board = *$board;
+                $body
+            }
+            AnyBoard::Coloured($board) => $body,
+            AnyBoard::LargeColoured($board) => {
+                let $board = *$board;
+                $body
+            }
+        }
+    };
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err(USAGE.to_string()),
+    };
+    match command {
+        "solve" => run_solve(args),
+        "generate" => run_generate(args),
+        "reverse-solve" => run_reverse_solve(args),
+        "solutions" => run_solutions(args),
+        "hint" => run_hint(args),
+        "tablebase" => run_tablebase(args),
+        "combine" => run_combine(args),
+        "verify" => run_verify(args),
+        "check" => run_check(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("unknown command '{}'\n\n{}", command, USAGE)),
+    }
+}
+
+fn run_solve(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "method", "heuristic"], &["symmetry"])?;
+    with_board!(args.any_board()?, |board| solve_board(board, &args))
+}
+
+fn solve_board<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    args: &Args,
+) -> Result<(), String> {
+    let use_symmetry = args.has("symmetry");
+    let heuristic: Heuristic<WORDS, PLANES> =
+        match args.get_or("heuristic", "lines".to_string())?.as_str() {
+            "movers" => movers_not_home,
+            "lines" => line_distance,
+            other => return Err(format!("unknown heuristic '{}'", other)),
+        };
+    let solution = match args.get_or("method", "bfs".to_string())?.as_str() {
+        "bfs" => solve_shortest(board, use_symmetry),
+        "astar" => solve_astar(board, heuristic, use_symmetry),
+        "ida" => solve_ida_star(board, heuristic, use_symmetry),
+        "bidirectional" => solve_bidirectional(board),
+        "dfs" => solve_dfs(board)?,
+        other => return Err(format!("unknown method '{}'", other)),
+    };
+
+    println!("{}", board);
+    print_solution(&solution);
+    if solution.can_be_solved {
+        verify_solution(&board, &solution.moves)
+            .map_err(|error| format!("solver returned a bad solution: {}", error))?;
+    }
+    Ok(())
+}
+
+// The depth-first search recurses once for every move on the path it is trying, which can be one
+// for every board it reaches: far more than fits on the main thread's stack.
+const DFS_STACK_SIZE: usize = 1 << 30;
+
+fn solve_dfs<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+) -> Result<Solution, String> {
+    thread::Builder::new()
+        .stack_size(DFS_STACK_SIZE)
+        .spawn(move || solve(board, &mut HashMap::new()))
+        .map_err(|error| format!("could not start the search: {}", error))?
+        .join()
+        .map_err(|_| "the search panicked".to_string())
+}
+
+fn print_solution(solution: &Solution) {
+    if solution.can_be_solved {
+        println!(
+            "Solution: {:?} {} moves, {} boards searched",
+            solution.moves, solution.move_count, solution.tree_size
+        );
+    } else {
+        println!("No solution, {} boards searched", solution.tree_size);
+    }
+}
+
+fn run_generate(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &[
+            "width",
+            "height",
+            "topology",
+            "max-barns",
+            "max-houses",
+            "max-empty",
+            "layouts",
+            "combinations",
+            "max-cows",
+            "max-people",
+            "max-people-without-houses",
+            "per-search",
+            "max-solutions",
+            "outside",
+            "output",
+        ],
+        &["elegant-in-search", "intended", "wrap"],
+    )?;
+    let topology = args.get_or("topology", Topology::Rect)?;
+    let (width, height): (u8, u8) = (args.require("width")?, args.require("height")?);
+    if width as usize * height as usize > LargeBoard::MAX_CELLS {
+        return Err(format!(
+            "boards can have at most {} cells",
+            LargeBoard::MAX_CELLS
+        ));
+    }
+    let wraps = args.has("wrap");
+    if wraps && !topology.can_wrap(width, height) {
+        return Err(format!(
+            "a {}x{} {} board can't wrap",
+            width,
+            height,
+            topology.directory()
+        ));
+    }
+    let outside = match args.get::<String>("outside")? {
+        Some(text) => parse_cells(&text, width as usize * height as usize)?,
+        None => Vec::new(),
+    };
+    let defaults = GenerateOptions::new(width, height, topology);
+    let options = GenerateOptions {
+        max_barns: args.get_or("max-barns", defaults.max_barns)?,
+        max_houses: args.get_or("max-houses", defaults.max_houses)?,
+        max_empty: args.get_or("max-empty", defaults.max_empty)?,
+        layouts_per_count: args.get_or("layouts", defaults.layouts_per_count)?,
+        max_combinations: args.get_or("combinations", defaults.max_combinations)?,
+        max_cows: args.get_or("max-cows", defaults.max_cows)?,
+        max_people: args.get_or("max-people", defaults.max_people)?,
+        max_people_without_houses: args.get_or(
+            "max-people-without-houses",
+            defaults.max_people_without_houses,
+        )?,
+        puzzles_per_search: args.get_or("per-search", defaults.puzzles_per_search)?,
+        elegant_in_search: args.has("elegant-in-search"),
+        max_optimal_solutions: args.get("max-solutions")?,
+        require_intended_solution: args.has("intended"),
+        wraps,
+        outside,
+        output_directory: args.get_or("output", defaults.output_directory.clone())?,
+        ..defaults
+    };
+    generate_puzzles(&options);
+    Ok(())
+}
+
+fn run_reverse_solve(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(
+        args,
+        &["topology", "cows", "people", "count"],
+        &["symmetry", "elegant"],
+    )?;
+    with_board!(args.any_board()?, |board| reverse_solve_board(board, &args))
+}
+
+fn reverse_solve_board<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    args: &Args,
+) -> Result<(), String> {
+    let (puzzles, iterations) = reverse_solve_deepest(
+        board,
+        args.require("cows")?,
+        args.require("people")?,
+        args.has("symmetry"),
+        args.get_or("count", 1)?,
+        args.has("elegant"),
+    );
+
+    for (puzzle_board, reverse_solution) in &puzzles {
+        let moves: Vec<Move> = reverse_solution.moves.iter().rev().cloned().collect();
+        println!("{}", puzzle_board);
+        println!("{}", puzzle_board.to_tagged_string());
+        println!(
+            "Solution: {:?} {} moves",
+            moves, reverse_solution.move_count
+        );
+        verify_solution(puzzle_board, &moves)
+            .map_err(|error| format!("reverse solver returned a bad solution: {}", error))?;
+    }
+    println!("{} puzzles, {} iterations", puzzles.len(), iterations);
+    Ok(())
+}
+
+// Counts the shortest solutions of a board, and lists the first --list of them.
+fn run_solutions(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "list"], &[])?;
+    with_board!(args.any_board()?, |board| list_solutions(board, &args))
+}
+
+fn list_solutions<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    args: &Args,
+) -> Result<(), String> {
+    let list: usize = args.get_or("list", 0)?;
+
+    println!("{}", board);
+    println!("{} optimal solutions", count_optimal_solutions(board));
+    for moves in optimal_solutions(board).take(list) {
+        println!("{:?}", moves);
+    }
+    Ok(())
+}
+
+fn run_hint(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "tablebase"], &[])?;
+    let board: Board = args.board()?;
+    let tablebase = match args.get::<String>("tablebase")? {
+        Some(path) => Some(Tablebase::load(&path).map_err(|error| format!("{}: {}", path, error))?),
+        None => None,
+    };
+
+    println!("{}", board);
+    match hint(&board, &mut HashMap::new(), tablebase.as_ref()) {
+        Hint::Solved => println!("Already solved"),
+        Hint::Moves { moves, distance } => {
+            println!("{} moves left, best next moves: {:?}", distance, moves)
+        }
+        Hint::Unsolvable => println!("Can no longer be solved"),
+    }
+    Ok(())
+}
+
+// Builds the tablebase for the layout of barns, houses and empty cells on the board; any cows or
+// people on it are ignored.
+fn run_tablebase(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "cows", "people", "output"], &[])?;
+    let layout: Board = args.board()?;
+    let output: String = args.require("output")?;
+    let tablebase = build_tablebase(layout, args.require("cows")?, args.require("people")?);
+    tablebase
+        .save(&output)
+        .map_err(|error| format!("could not write {}: {}", output, error))?;
+    println!("{} solvable boards written to {}", tablebase.len(), output);
+    Ok(())
+}
+
+fn run_combine(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["rect-dir", "hex-dir", "per-bucket"], &[])?;
+    let defaults = CombineOptions::default();
+    let options = CombineOptions {
+        directories: vec![
+            (
+                Topology::Rect,
+                args.get_or("rect-dir", Topology::Rect.directory().to_string())?,
+            ),
+            (
+                Topology::Hex,
+                args.get_or("hex-dir", Topology::Hex.directory().to_string())?,
+            ),
+        ],
+        puzzles_per_bucket: args.get_or("per-bucket", defaults.puzzles_per_bucket)?,
+    };
+    combine_puzzles(&options);
+    Ok(())
+}
+
+// Re-solves rows of generated puzzle files and checks the optimal move count matches the one the
+// reverse solver recorded.
+fn run_verify(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "every"], &[])?;
+    let every: usize = args.get_or("every", 1)?;
+    if every == 0 {
+        return Err("--every must be at least 1".to_string());
+    }
+
+    let mut files: Vec<(Topology, String)> = Vec::new();
+    if args.positional.is_empty() {
+        for topology in Topology::ALL {
+            let entries = match fs::read_dir(topology.directory()) {
+                Ok(entries) => entries,
+                // nothing generated for this topology yet
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(format!("{}: {}", topology.directory(), error)),
+            };
+            let mut paths: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path().to_string_lossy().to_string())
+                .collect();
+            paths.sort();
+            files.extend(paths.into_iter().map(|path| (topology, path)));
+        }
+    } else {
+        let topology = args.get_or("topology", Topology::Rect)?;
+        files.extend(args.positional.iter().map(|path| (topology, path.clone())));
+    }
+
+    let mut checked = 0;
+    let mut failures = 0;
+    for (topology, file) in files {
+        let contents = fs::read_to_string(&file).map_err(|error| format!("{}: {}", file, error))?;
+        for (i, line) in contents.lines().enumerate().step_by(every) {
+            let line_number = i + 1;
+            checked += 1;
+            let columns: Vec<&str> = line.split('\t').collect();
+            let board = match AnyBoard::parse(columns[0], Some(topology)) {
+                Ok(board) => board,
+                Err(error) => {
+                    println!("{}: {}", file, error.with_line(line_number));
+                    failures += 1;
+                    continue;
+                }
+            };
+            let recorded = columns.get(2).copied().unwrap_or("?");
+            let failure = with_board!(board, |board| verify_row(board, recorded));
+            if let Some(failure) = failure {
+                println!("{}: line {}: {} {}", file, line_number, columns[0], failure);
+                failures += 1;
+            }
+        }
+    }
+
+    println!("{} puzzles checked, {} failed", checked, failures);
+    if failures > 0 {
+        return Err(format!("{} puzzles failed verification", failures));
+    }
+    Ok(())
+}
+
+// What is wrong with a puzzle file row, if anything, given its board and recorded move count.
+fn verify_row<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    recorded: &str,
+) -> Option<String> {
+    let expected = recorded.parse::<u16>().ok();
+    let solution = solve_shortest(board, false);
+    if solution.can_be_solved {
+        if let Err(error) = verify_solution(&board, &solution.moves) {
+            return Some(format!("solution does not replay: {}", error));
+        }
+    }
+    if !solution.can_be_solved || Some(solution.move_count) != expected {
+        return Some(format!(
+            "recorded as {} moves, solves in {}",
+            recorded,
+            if solution.can_be_solved {
+                solution.move_count.to_string()
+            } else {
+                "never".to_string()
+            }
+        ));
+    }
+    None
+}
+
+// Replays a move list against a board and reports whether it is a solution.
+fn run_check(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["topology", "moves"], &[])?;
+    let moves = parse_moves(&args.require::<String>("moves")?)?;
+    with_board!(args.any_board()?, |board| verify_solution(&board, &moves))
+        .map_err(|error| format!("not a solution: {}", error))?;
+    println!("Solves the board in {} moves", moves.len());
+    Ok(())
+}
+
+// comma separated indices of cells on a board with `cells` of them
+fn parse_cells(text: &str, cells: usize) -> Result<Vec<u16>, String> {
+    text.split(',')
+        .map(|cell| match cell.parse::<u16>() {
+            Ok(index) if (index as usize) < cells => Ok(index),
+            _ => Err(format!(
+                "invalid cell '{}', expected a number below {}",
+                cell, cells
+            )),
+        })
+        .collect()
+}
+
+fn parse_moves(text: &str) -> Result<Vec<Move>, String> {
+    text.split_whitespace()
+        .map(|m| {
+            let cells: Vec<u16> = m
+                .split(',')
+                .map(|cell| cell.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid move '{}'", m))?;
+            match cells.as_slice() {
+                [from, to, puller] => Ok(Move {
+                    from: *from,
+                    to: *to,
+                    puller: *puller,
+                }),
+                _ => Err(format!("invalid move '{}', expected from,to,puller", m)),
+            }
+        })
+        .collect()
+}
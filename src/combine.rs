@@ -1,15 +1,33 @@
 extern crate rand;
-use std::{fs, collections::HashMap};
+use std::fs;
 
-use crate::board::Board;
+use crate::board::{LargeColouredBoard, Piece, Topology};
 
-pub fn combine_puzzles() {
-    let directories = vec!["rect", "hex"];
+pub struct CombineOptions {
+    // folders of generated puzzle files to read, with the topology of the boards in them
+    pub directories: Vec<(Topology, String)>,
+    pub puzzles_per_bucket: u8,
+}
+
+impl Default for CombineOptions {
+    fn default() -> CombineOptions {
+        CombineOptions {
+            // the game's puzzle lists only have rect and hex boards
+            directories: [Topology::Rect, Topology::Hex]
+                .iter()
+                .map(|topology| (*topology, topology.directory().to_string()))
+                .collect(),
+            puzzles_per_bucket: 30,
+        }
+    }
+}
 
-    let mut rows: Vec<(String, u8, u32, f32)> = Vec::new();
+pub fn combine_puzzles(options: &CombineOptions) {
+    let mut rows: Vec<(String, u16, u32, f32)> = Vec::new();
 
     // loop through the folders in each directory
-    for directory in directories {
+    for (topology, directory) in &options.directories {
+        let topology = *topology;
         // loop through the files in each folder
         let files = fs::read_dir(directory).unwrap();
         for file in files {
@@ -20,7 +38,7 @@ pub fn combine_puzzles() {
             let contents = fs::read_to_string(file).unwrap();
             // split the file into lines
             let lines: Vec<&str> = contents.lines().collect();
-            
+
             let mut line_number = 0;
             // loop through the lines
             for line in lines {
@@ -29,21 +47,45 @@ pub fn combine_puzzles() {
                     println!("{} {}", file, line_number);
                 }
                 let columns: Vec<&str> = line.split('\t').collect();
+                let mover_count = match LargeColouredBoard::from_string(columns[0], topology) {
+                    Err(error) => {
+                        println!("{}: {}", file, error.with_line(line_number));
+                        continue;
+                    }
+                    // the game has no boards whose edges wrap, coloured herds or cells outside the
+                    // board
+                    Ok(board)
+                        if board.wraps
+                            || board.has_coloured_pieces()
+                            || board.count_piece(Piece::Outside) > 0 =>
+                    {
+                        continue
+                    }
+                    Ok(board) => board.mover_mask().len(),
+                };
                 let board = columns[0].to_string();
-                let moves = columns[2].parse::<u8>().unwrap();
-                let iterations = columns[3].parse::<u32>().unwrap();
-                let person_count = board.matches('P').count();
-                let cow_count = board.matches('O').count();
-                let mover_count = cow_count + person_count;
+                let counts = columns
+                    .get(2)
+                    .zip(columns.get(3))
+                    .and_then(|(moves, iterations)| {
+                        Some((moves.parse::<u16>().ok()?, iterations.parse::<u32>().ok()?))
+                    });
+                let Some((moves, iterations)) = counts else {
+                    println!(
+                        "{}: line {}, expected move and iteration counts in columns 3 and 4",
+                        file, line_number
+                    );
+                    continue;
+                };
                 let difficulty =
                     (moves as f32) * f32::log2(iterations as f32) / (mover_count as f32);
-                let h_r = if directory == "rect" { "R" } else { "H" };
+                let h_r = topology.prefix();
                 let board_string = format!("{}~{}{}", moves, h_r, board);
                 if (moves as f32) / (mover_count as f32) < 2.0 {
                     // it's not interesting when the pieces can move directly into the goal
                     continue;
                 }
-                if difficulty < (30 as f32) && board.contains("E") {
+                if difficulty < 30_f32 && board.contains("E") {
                     // it's not interesting when the empty space is not necessary
                     // too expensive to check if it's necessary, so just ignoring for low difficulties
                     continue;
@@ -55,28 +97,36 @@ pub fn combine_puzzles() {
 
     // sort the rows by difficulty
     rows.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-    rows.dedup_by(|a, b|a.0 == b.0);
+    rows.dedup_by(|a, b| a.0 == b.0);
     rows.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap());
-    rows.dedup_by(|a, b|(a.3 - b.3).abs() < 0.001);
+    rows.dedup_by(|a, b| (a.3 - b.3).abs() < 0.001);
 
     let min_difficulty = rows[0].3;
     let max_difficulty = rows[rows.len() - 1].3;
     println!("Max difficulty: {}", max_difficulty);
     println!("Min difficulty: {}", min_difficulty);
-    let difficulty_span = max_difficulty - min_difficulty;
-
-    let easy_bucket : Vec<(String, u8, u32, f32)> = rows.iter().filter(|x| x.3  < 22.5).map(|x| x.clone()).collect();
-    let moderate_bucket : Vec<(String, u8, u32, f32)> = rows.iter().filter(|x| x.3 >= 27.5 && x.3 < 40.0).map(|x| x.clone()).collect();
-    let hard_bucket : Vec<(String, u8, u32, f32)> = rows.iter().filter(|x| x.3 >= 50.0 && x.3 - min_difficulty < 65.0).map(|x| x.clone()).collect();
-    let insane_bucket : Vec<(String, u8, u32, f32)> = rows.iter().filter(|x| x.3 >= 75.0).map(|x| x.clone()).collect();
 
+    let easy_bucket: Vec<(String, u16, u32, f32)> =
+        rows.iter().filter(|x| x.3 < 22.5).cloned().collect();
+    let moderate_bucket: Vec<(String, u16, u32, f32)> = rows
+        .iter()
+        .filter(|x| x.3 >= 27.5 && x.3 < 40.0)
+        .cloned()
+        .collect();
+    let hard_bucket: Vec<(String, u16, u32, f32)> = rows
+        .iter()
+        .filter(|x| x.3 >= 50.0 && x.3 - min_difficulty < 65.0)
+        .cloned()
+        .collect();
+    let insane_bucket: Vec<(String, u16, u32, f32)> =
+        rows.iter().filter(|x| x.3 >= 75.0).cloned().collect();
 
     println!("Easy: {}", easy_bucket.len());
     println!("Moderate: {}", moderate_bucket.len());
     println!("Hard: {}", hard_bucket.len());
     println!("Insane: {}", insane_bucket.len());
 
-    let puzzles_per_bucket = 30;
+    let puzzles_per_bucket = options.puzzles_per_bucket;
 
     let easy_reduced = sample_balance_board_type(easy_bucket, puzzles_per_bucket);
     let moderate_reduced = sample_balance_board_type(moderate_bucket, puzzles_per_bucket);
@@ -91,35 +141,49 @@ pub fn combine_puzzles() {
     println!("{} puzzles", rows.len());
 }
 
-fn print_puzzles(rows: Vec<(String, u8, u32, f32)>, difficulty: String) {
+fn print_puzzles(rows: Vec<(String, u16, u32, f32)>, difficulty: String) {
     for row in rows {
         println!("{{difficulty:'{}', board:'{}'}},", difficulty, row.0);
     }
 }
 
-fn sample_balance_board_type(rows: Vec<(String, u8, u32, f32)>, length: u8) -> Vec<(String, u8, u32, f32)> {
-    let hex_rows = rows.iter().filter(|x| x.0.contains("~H")).map(|x| x.clone()).collect();
-    let rect_rows = rows.iter().filter(|x| x.0.contains("~R")).map(|x| x.clone()).collect();
+fn sample_balance_board_type(
+    rows: Vec<(String, u16, u32, f32)>,
+    length: u8,
+) -> Vec<(String, u16, u32, f32)> {
+    let hex_rows = rows
+        .iter()
+        .filter(|x| x.0.contains("~H"))
+        .cloned()
+        .collect();
+    let rect_rows = rows
+        .iter()
+        .filter(|x| x.0.contains("~R"))
+        .cloned()
+        .collect();
     let mut rect_sample = sample_by_difficulty(rect_rows, length / 2);
     let mut hex_sample = sample_by_difficulty(hex_rows, length / 2);
     // zip together the samples
     let mut sample = Vec::new();
     for _i in 0..length / 2 {
-        if rect_sample.len() > 0 {
+        if !rect_sample.is_empty() {
             sample.push(rect_sample.remove(0));
         }
-        if hex_sample.len() > 0 {
+        if !hex_sample.is_empty() {
             sample.push(hex_sample.remove(0));
         }
     }
-    
+
     sample
 }
 
-fn sample_by_difficulty(rows: Vec<(String, u8, u32, f32)>, length: u8) -> Vec<(String, u8, u32, f32)> {
+fn sample_by_difficulty(
+    rows: Vec<(String, u16, u32, f32)>,
+    length: u8,
+) -> Vec<(String, u16, u32, f32)> {
     let mut rows = rows.clone();
     rows.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap());
-    
+
     if rows.len() < length as usize {
         return rows.to_vec();
     }
@@ -129,10 +193,10 @@ fn sample_by_difficulty(rows: Vec<(String, u8, u32, f32)>, length: u8) -> Vec<(S
     let step_size = difficulty_span / ((length) as f32);
     let mut reduced_rows = Vec::new();
     let mut difficulty = min_difficulty;
-    for i in 0..rows.len() {
-        if rows[i].3 >= difficulty {
+    for row in &rows {
+        if row.3 >= difficulty {
             difficulty += step_size;
-            reduced_rows.push(rows[i].clone());
+            reduced_rows.push(row.clone());
         }
         if reduced_rows.len() == length as usize {
             break;
@@ -0,0 +1,300 @@
+use crate::board::{Board, CellSet, Piece};
+
+// Finds boards that can never be solved. The checks are sound: a board they reject has no
+// solution. They only look at where goals, empty cells and the board's edges are, which never
+// changes, so that is worked out once in new and every board checked must share its layout with the
+// one given there.
+pub struct DeadlockDetector<const WORDS: usize = 1> {
+    // masks of the layout's goals, empty cells and cells outside the board, to check boards share it
+    layout: Vec<CellSet<WORDS>>,
+    // the herds with pieces or a goal on the board, the only ones boards after it can have pieces of
+    herds: Vec<(Piece, PieceReach<WORDS>)>,
+}
+
+// what pieces of one kind can reach on the layout, and how they get home if their goal is on it
+struct PieceReach<const WORDS: usize> {
+    needs_home: bool,
+    // cells from which a piece can get home while other pieces are around to pull it
+    home_with_help: CellSet<WORDS>,
+    // cells from which a piece can get home when no other piece is left on the board
+    home_alone: CellSet<WORDS>,
+    // for each cell, the cells a piece starting there could ever stand on
+    reachable: Vec<CellSet<WORDS>>,
+}
+
+impl<const WORDS: usize> DeadlockDetector<WORDS> {
+    pub fn new<const PLANES: usize>(board: &Board<WORDS, PLANES>) -> DeadlockDetector<WORDS> {
+        DeadlockDetector {
+            layout: layout_masks(board),
+            herds: Piece::HERDS
+                .iter()
+                .filter(|&&(mover, goal)| {
+                    board.count_piece(mover) > 0 || board.count_piece(goal) > 0
+                })
+                .map(|&(mover, goal)| (mover, PieceReach::new(board, mover, goal)))
+                .collect(),
+        }
+    }
+
+    pub fn is_dead<const PLANES: usize>(&self, board: &Board<WORDS, PLANES>) -> bool {
+        debug_assert!(layout_masks(board) == self.layout);
+        let mut movers = CellSet::new();
+        let mut needy = CellSet::new();
+        for (mover, reach) in &self.herds {
+            let pieces = board.piece_mask(*mover);
+            movers = movers | pieces;
+            if reach.needs_home {
+                // A piece that can't get home from where it is, however the others move, is stuck
+                // for good.
+                if !(pieces & !reach.home_with_help).is_empty() {
+                    return true;
+                }
+                needy = needy | pieces;
+            }
+        }
+
+        // Pieces without a goal never leave, so with none of them on the board, the last piece to go
+        // home goes alone and has to be on a cell it can get home from alone. With more than one
+        // left, nothing more follows: a piece that can get home at all can get onto a line with
+        // its goal, and it can get home alone from there.
+        let staying = movers & !needy;
+        if !staying.is_empty() || needy.len() != 1 {
+            return false;
+        }
+        self.herds
+            .iter()
+            .any(|(mover, reach)| !(board.piece_mask(*mover) & !reach.home_alone).is_empty())
+    }
+
+    // For each kind of piece, the cells the pieces of that kind on `board` could ever stand on.
+    // Kinds with no pieces on `board` get an empty mask, as no later board can have any.
+    pub fn reachable_from<const PLANES: usize>(
+        &self,
+        board: &Board<WORDS, PLANES>,
+    ) -> [(Piece, CellSet<WORDS>); Piece::HERDS.len()] {
+        Piece::HERDS.map(|(mover, _)| {
+            let mut cells = CellSet::new();
+            if let Some((_, reach)) = self.herds.iter().find(|(herd, _)| *herd == mover) {
+                for i in board.piece_mask(mover).iter() {
+                    cells = cells | reach.reachable[i as usize];
+                }
+            }
+            (mover, cells)
+        })
+    }
+}
+
+// shortcut for checking a single board
+pub fn is_dead<const WORDS: usize, const PLANES: usize>(board: &Board<WORDS, PLANES>) -> bool {
+    DeadlockDetector::new(board).is_dead(board)
+}
+
+fn layout_masks<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+) -> Vec<CellSet<WORDS>> {
+    Piece::HERDS
+        .iter()
+        .map(|&(_, goal)| goal)
+        .chain([Piece::Empty, Piece::Outside])
+        .map(|piece| board.piece_mask(piece))
+        .collect()
+}
+
+impl<const WORDS: usize> PieceReach<WORDS> {
+    fn new<const PLANES: usize>(
+        board: &Board<WORDS, PLANES>,
+        mover: Piece,
+        goal: Piece,
+    ) -> PieceReach<WORDS> {
+        let mut reach = PieceReach {
+            needs_home: board.needs_home(mover),
+            home_with_help: CellSet::new(),
+            home_alone: CellSet::new(),
+            reachable: Vec::new(),
+        };
+        for i in 0..board.cell_count() as u16 {
+            let (reachable, home) = reachable_cells(board, i, goal, true);
+            reach.reachable.push(reachable);
+            if !reach.needs_home {
+                continue;
+            }
+            if home {
+                reach.home_with_help.insert(i);
+            }
+            if reachable_cells(board, i, goal, false).1 {
+                reach.home_alone.insert(i);
+            }
+        }
+        reach
+    }
+}
+
+// The cells a piece starting at `start` could stand on, and whether it could get into a `goal`.
+// A piece slides along a line into a `goal`, or stops on the cell before a piece or a goal of
+// another kind; it can't slide towards an empty cell or off the board. Pieces could stand on any
+// cell that isn't a goal or empty, so with `helped` any such cell can stop a slide; without it,
+// only another kind's goal can, as for a piece that is alone on the board.
+fn reachable_cells<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+    start: u16,
+    goal: Piece,
+    helped: bool,
+) -> (CellSet<WORDS>, bool) {
+    let mut reachable = CellSet::new();
+    reachable.insert(start);
+    let mut to_visit = vec![start];
+    let mut home = false;
+    while let Some(index) = to_visit.pop() {
+        for &direction in board.topology.directions() {
+            let mut prev_space: Option<u16> = None;
+            for space in board.ray(index, direction) {
+                let piece = board.get_index(space as usize);
+                if piece == Piece::Empty {
+                    break;
+                }
+                if piece == goal {
+                    home = true;
+                    break;
+                }
+                let other_goal = piece.is_goal();
+                if other_goal || helped {
+                    if let Some(prev_space) = prev_space {
+                        if !reachable.contains(prev_space) {
+                            reachable.insert(prev_space);
+                            to_visit.push(prev_space);
+                        }
+                    }
+                }
+                if other_goal {
+                    break;
+                }
+                prev_space = Some(space);
+            }
+        }
+    }
+    (reachable, home)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{ColouredBoard, Topology};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use std::collections::{HashMap, HashSet};
+
+    // Every board reachable from `board`, and which of them can still be solved, found by trying
+    // every move without the detector.
+    fn solvable_boards(board: ColouredBoard) -> (Vec<ColouredBoard>, HashSet<ColouredBoard>) {
+        let mut previous: HashMap<ColouredBoard, Vec<ColouredBoard>> =
+            HashMap::from([(board, Vec::new())]);
+        let mut to_visit = vec![board];
+        while let Some(board) = to_visit.pop() {
+            for (next_board, _, _, _) in board.get_possible_moves() {
+                previous
+                    .entry(next_board)
+                    .or_insert_with(|| {
+                        to_visit.push(next_board);
+                        Vec::new()
+                    })
+                    .push(board);
+            }
+        }
+        let mut solvable: HashSet<ColouredBoard> = previous
+            .keys()
+            .filter(|board| board.is_solved())
+            .copied()
+            .collect();
+        let mut to_visit: Vec<ColouredBoard> = solvable.iter().copied().collect();
+        while let Some(board) = to_visit.pop() {
+            for previous_board in &previous[&board] {
+                if solvable.insert(*previous_board) {
+                    to_visit.push(*previous_board);
+                }
+            }
+        }
+        (previous.into_keys().collect(), solvable)
+    }
+
+    fn random_board(rng: &mut StdRng, s: &str, topology: Topology) -> ColouredBoard {
+        let pieces = [
+            Piece::Barn,
+            Piece::Cow,
+            Piece::Person,
+            Piece::House,
+            Piece::Empty,
+            Piece::Cow,
+            Piece::RedBarn,
+            Piece::RedCow,
+        ];
+        let mut board = Board::from_string(s, topology).unwrap();
+        let mut cells: Vec<usize> = (0..board.cell_count())
+            .filter(|&i| board.get_index(i) == Piece::Blank)
+            .collect();
+        cells.shuffle(rng);
+        let count = cells.len() / 2;
+        for &i in &cells[..count] {
+            board.set_index(i, *pieces.choose(rng).unwrap());
+        }
+        board
+    }
+
+    // Which of is_dead's rules rejects `board`: 0 for a piece that can't get home, 1 for a last
+    // piece that can't get home alone.
+    fn dead_rule(detector: &DeadlockDetector, board: &ColouredBoard) -> Option<usize> {
+        if !detector.is_dead(board) {
+            return None;
+        }
+        let stuck = detector.herds.iter().any(|(mover, reach)| {
+            reach.needs_home && !(board.piece_mask(*mover) & !reach.home_with_help).is_empty()
+        });
+        Some(if stuck { 0 } else { 1 })
+    }
+
+    // the boards each rule rejects, out of everything reachable from random starts
+    #[test]
+    fn dead_boards_cannot_be_solved() {
+        let mut rng = StdRng::seed_from_u64(20);
+        let mut rule_counts = [0; 2];
+        for (s, topology) in [
+            ("3|3|_________", Topology::Rect),
+            ("4|3|____________", Topology::Rect),
+            ("4|3|____________", Topology::Hex),
+            ("3|3|_________", Topology::Hex),
+            ("4|2|________", Topology::Triangle),
+            ("3|3|_________", Topology::Triangle),
+            ("3|3|_________|wrap", Topology::Rect),
+            ("4|3|._____._____|wrap", Topology::Rect),
+        ] {
+            for _ in 0..100 {
+                let start = random_board(&mut rng, s, topology);
+                let detector = DeadlockDetector::new(&start);
+                let (boards, solvable) = solvable_boards(start);
+                for board in boards {
+                    if let Some(rule) = dead_rule(&detector, &board) {
+                        rule_counts[rule] += 1;
+                        assert!(
+                            !solvable.contains(&board),
+                            "{} can be solved, but rule {} rejects it",
+                            board.to_tagged_string(),
+                            rule
+                        );
+                    }
+                }
+            }
+        }
+        assert!(
+            rule_counts.iter().all(|&count| count > 0),
+            "{:?}",
+            rule_counts
+        );
+    }
+
+    #[test]
+    fn lone_cow_needs_a_barn_on_its_lines() {
+        let dead = ColouredBoard::from_string("3|3|B___O____", Topology::Rect).unwrap();
+        assert!(is_dead(&dead));
+        assert!(solvable_boards(dead).1.is_empty());
+        let alive = ColouredBoard::from_string("3|3|B__O_____", Topology::Rect).unwrap();
+        assert!(!is_dead(&alive));
+    }
+}
@@ -1,91 +1,178 @@
-use std::collections::{HashSet,};
-use std::fs::{ File};
+use std::collections::HashSet;
+use std::fs::{self, File};
 use std::io::Write;
 
-
 use rand::Rng;
 
-use crate::board::{Board, Piece};
-use crate::reverse_solver::reverse_solve;
-use crate::settings::HEXAGONAL_MODE;
-use crate::solve::{Solution};
+use crate::board::{Board, Piece, Topology, LARGE_BOARD_WORDS};
+use crate::reverse_solver::{reverse_solve_deepest, ReverseSolution};
+use crate::solve::{count_layer_solutions, shortest_solution_layers};
+
+// Everything generate_puzzles can be tuned with. The defaults from new are the settings the
+// rect/ and hex/ files were generated with.
+pub struct GenerateOptions {
+    pub width: u8,
+    pub height: u8,
+    pub topology: Topology,
+    // boards whose edges wrap, written to their own files next to the others
+    pub wraps: bool,
+    // cells left off the board, for puzzles that aren't rectangles; written to a file for each shape
+    pub outside: Vec<u16>,
+    pub max_barns: u8,
+    pub max_houses: u8,
+    // boards with more than one barn get one empty cell fewer
+    pub max_empty: u8,
+    // layouts sampled for each combination of barn, house and empty counts
+    pub layouts_per_count: usize,
+    // layout and piece count combinations sampled to reverse solve
+    pub max_combinations: usize,
+    pub max_cows: u8,
+    pub max_people: u8,
+    pub max_people_without_houses: u8,
+    // deepest puzzles kept from each reverse search, no two symmetric; those that aren't elegant are
+    // then dropped
+    pub puzzles_per_search: usize,
+    // pass over boards that aren't elegant during the search instead, so a search whose deepest
+    // boards aren't elegant still gives the deepest ones that are
+    pub elegant_in_search: bool,
+    // puzzles with more shortest solutions than this are dropped
+    pub max_optimal_solutions: Option<u64>,
+    // drop puzzles that can be solved in fewer moves than the reverse search took to build them
+    pub require_intended_solution: bool,
+    // defaults to the topology's directory
+    pub output_directory: String,
+}
 
-const DIRECTORY: &str = if HEXAGONAL_MODE { "hex" } else { "rect" };
+impl GenerateOptions {
+    pub fn new(width: u8, height: u8, topology: Topology) -> GenerateOptions {
+        GenerateOptions {
+            width,
+            height,
+            topology,
+            wraps: false,
+            outside: Vec::new(),
+            max_barns: 2,
+            max_houses: 1,
+            max_empty: 2,
+            layouts_per_count: 128,
+            max_combinations: 8192,
+            max_cows: 5,
+            max_people: 5,
+            max_people_without_houses: 3,
+            puzzles_per_search: 1,
+            elegant_in_search: false,
+            max_optimal_solutions: None,
+            require_intended_solution: false,
+            output_directory: topology.directory().to_string(),
+        }
+    }
+}
 
+// Boards too big for the default Board are generated as LargeBoards.
+pub fn generate_puzzles(options: &GenerateOptions) {
+    if options.width as usize * options.height as usize <= Board::<1>::MAX_CELLS {
+        generate_puzzles_on::<1>(options);
+    } else {
+        generate_puzzles_on::<LARGE_BOARD_WORDS>(options);
+    }
+}
 
-pub fn generate_puzzles(width: u8, height: u8) {
-    let mut initial_boards : Vec<Board> = Vec::new();
-    for barn_count in 1..3 {
-        for house_count in 0..2 {
-            let mut max_empty = 3;
-            if barn_count == 2 {
-                max_empty = 2;
+fn generate_puzzles_on<const WORDS: usize>(options: &GenerateOptions) {
+    let width = options.width;
+    let height = options.height;
+    let wraps = options.wraps;
+    let mut initial_boards: Vec<Board<WORDS>> = Vec::new();
+    for barn_count in 1..=options.max_barns {
+        for house_count in 0..=options.max_houses {
+            let mut max_empty = options.max_empty;
+            if barn_count > 1 {
+                max_empty = max_empty.saturating_sub(1);
             }
-            for empty_count in 0..max_empty {
-                let boards = get_initial_boards(width, height, barn_count, house_count, empty_count);
-                println!("{} boards with {} barns, {} houses, {} empty", boards.len(), barn_count, house_count, empty_count);
-                let mut sample = random_sample(boards, 128);
+            for empty_count in 0..=max_empty {
+                let boards = get_initial_boards(options, barn_count, house_count, empty_count);
+                println!(
+                    "{} boards with {} barns, {} houses, {} empty",
+                    boards.len(),
+                    barn_count,
+                    house_count,
+                    empty_count
+                );
+                let mut sample = random_sample(boards, options.layouts_per_count);
                 initial_boards.append(&mut sample);
             }
         }
     }
     println!("Initial boards: {}", initial_boards.len());
 
-    let length = width * height;
-
-    let mut piece_combinations : Vec<(Board, u8, u8)> = Vec::new();
+    let mut piece_combinations: Vec<(Board<WORDS>, u8, u8)> = Vec::new();
     for board in initial_boards {
-        let max_cow_count : u8 = 5;
-        let mut max_person_count : u8 = 5;
+        let length = board.cell_count() - board.count_piece(Piece::Outside);
+        let max_cow_count = options.max_cows;
+        let mut max_person_count = options.max_people;
         let houses = board.count_piece(Piece::House);
         let barns = board.count_piece(Piece::Barn);
         let empty = board.count_piece(Piece::Empty);
         if houses == 0 {
-            max_person_count = 3;
+            max_person_count = options.max_people_without_houses;
         }
-        
+
         for cows in 1..(max_cow_count + 1) {
             for people in 1..(max_person_count + 1) {
-                if(cows + people + barns + houses + empty) > length - 2 
-                {
+                if cows as usize + people as usize + barns + houses + empty + 2 > length {
                     // must have at least 2 empty spaces
                     continue;
                 }
-                if cows + people > length / 2 {
+                if (cows + people) as usize > length / 2 {
                     // moving pieces can't fill more than half the board
                     continue;
                 }
-                piece_combinations.push((board.clone(), cows, people));
+                piece_combinations.push((board, cows, people));
             }
         }
     }
 
-    piece_combinations = random_sample(piece_combinations, 8192);
+    piece_combinations = random_sample(piece_combinations, options.max_combinations);
 
-    let mut output_rows : Vec<(Board, String, u8, usize)> = Vec::new();
+    let mut output_rows: Vec<(Board<WORDS>, String, u16, usize)> = Vec::new();
 
-    let mut completed = 0;
     let total = piece_combinations.len();
-    for (board, cows, people) in piece_combinations {
+    for (completed, (board, cows, people)) in piece_combinations.into_iter().enumerate() {
         let percent = (completed as f32 / total as f32) * 100.0;
         let houses = board.count_piece(Piece::House);
         let barns = board.count_piece(Piece::Barn);
         let empty = board.count_piece(Piece::Empty);
-        let description_string = format!("{}_{} {}_{}_{}_{}_{}", width, height, cows, people, houses, barns, empty);
-        println!("{}\t{} / {} = {}%", description_string, completed, total, percent);
-        let (puzzle_board, reverse_solution, iterations) = reverse_solve(board.clone(), cows, people);
-        let solution = Solution {
-            moves: reverse_solution.moves.into_iter().rev().collect(),
-            move_count: reverse_solution.move_count,
-            tree_size: iterations,
-            can_be_solved: true,
-        };
-        let is_elegant = solution.is_elegant(&puzzle_board);
-        //println!("Board: {} Elegant? {}  {} moves, {} iterations", puzzle_board.to_string(), is_elegant, reverse_solution.move_count, iterations);
-        if is_elegant {
-            output_rows.push((puzzle_board, description_string, reverse_solution.move_count, iterations));
+        let description_string = format!(
+            "{}_{} {}_{}_{}_{}_{}",
+            width, height, cows, people, houses, barns, empty
+        );
+        println!(
+            "{}\t{} / {} = {}%",
+            description_string, completed, total, percent
+        );
+        let (puzzles, iterations) = reverse_solve_deepest(
+            board,
+            cows,
+            people,
+            false,
+            options.puzzles_per_search,
+            options.elegant_in_search,
+        );
+        for (puzzle_board, reverse_solution) in puzzles {
+            if !options.elegant_in_search
+                && !reverse_solution.to_solution().is_elegant(&puzzle_board)
+            {
+                continue;
+            }
+            if !has_intended_solution(options, &puzzle_board, &reverse_solution) {
+                continue;
+            }
+            output_rows.push((
+                puzzle_board,
+                description_string.clone(),
+                reverse_solution.move_count,
+                iterations,
+            ));
         }
-        completed += 1;
     }
 
     // write to file
@@ -94,30 +181,89 @@ pub fn generate_puzzles(width: u8, height: u8) {
         .map(|row| format!("{}\t{}\t{}\t{}", row.0.to_string(), row.1, row.2, row.3))
         .collect::<Vec<String>>();
 
-    let file_name = format!("{}_{}.txt", width, height);
+    let mut file_name = format!("{}_{}", width, height);
+    if wraps {
+        file_name.push_str("_wrap");
+    }
+    if !options.outside.is_empty() {
+        // named by the cells left out, so each shape gets a file of its own
+        let mut outside = options.outside.clone();
+        outside.sort_unstable();
+        outside.dedup();
+        let cells: Vec<String> = outside.iter().map(|cell| cell.to_string()).collect();
+        file_name.push_str(&format!("_shaped_{}", cells.join("-")));
+    }
+    file_name.push_str(".txt");
     let data = lines.join("\n");
-    let mut f = File::create(format!("{}/{}", DIRECTORY, file_name))
+    fs::create_dir_all(&options.output_directory).expect("Unable to create output directory");
+    let mut f = File::create(format!("{}/{}", options.output_directory, file_name))
         .expect("Unable to create file");
     f.write_all(data.as_bytes()).expect("Unable to write data");
+}
 
+// Whether the puzzle passes the uniqueness checks options asks for: the reverse search's line is a
+// shortest solution, and there are few enough shortest solutions for it to be the intended one.
+fn has_intended_solution<const WORDS: usize>(
+    options: &GenerateOptions,
+    board: &Board<WORDS>,
+    reverse_solution: &ReverseSolution,
+) -> bool {
+    if options.max_optimal_solutions.is_none() && !options.require_intended_solution {
+        return true;
+    }
+    let layers = match shortest_solution_layers(*board) {
+        Some(layers) => layers,
+        None => return false,
+    };
+    if options.require_intended_solution && layers.len() - 1 != reverse_solution.move_count as usize
+    {
+        return false;
+    }
+    match options.max_optimal_solutions {
+        Some(max_optimal_solutions) => count_layer_solutions(&layers) <= max_optimal_solutions,
+        None => true,
+    }
 }
 
-fn get_initial_boards(width: u8, height: u8, barn_count : u8, house_count: u8, empty_count: u8) -> Vec<Board> {
-    return  generate_boards(width, height, 0, barn_count, 0, house_count, empty_count);
+fn get_initial_boards<const WORDS: usize>(
+    options: &GenerateOptions,
+    barn_count: u8,
+    house_count: u8,
+    empty_count: u8,
+) -> Vec<Board<WORDS>> {
+    generate_boards(
+        options.width,
+        options.height,
+        options.topology,
+        options.wraps,
+        &options.outside,
+        0,
+        barn_count,
+        0,
+        house_count,
+        empty_count,
+    )
 }
 
-pub fn generate_boards(
+#[allow(clippy::too_many_arguments)]
+pub fn generate_boards<const WORDS: usize>(
     width: u8,
     height: u8,
+    topology: Topology,
+    wraps: bool,
+    outside: &[u16],
     cow_count: u8,
     barn_count: u8,
     person_count: u8,
     house_count: u8,
     empty_count: u8,
-) -> Vec<Board> {
-    let length = width * height;
+) -> Vec<Board<WORDS>> {
+    let cells = width as usize * height as usize;
+    let on_board: Vec<usize> = (0..cells)
+        .filter(|&i| !outside.contains(&(i as u16)))
+        .collect();
     let strings = generate_with_prefix(
-        length,
+        on_board.len(),
         "",
         cow_count,
         barn_count,
@@ -125,11 +271,20 @@ pub fn generate_boards(
         house_count,
         empty_count,
     );
-    let mut boards: Vec<Board> = Vec::new();
-    let mut encountered_variants: HashSet<Board> = HashSet::new();
+    let mut boards: Vec<Board<WORDS>> = Vec::new();
+    let mut encountered_variants: HashSet<Board<WORDS>> = HashSet::new();
     for string in strings {
-        let full_string = format!("{}|{}|{}", width, height, string);
-        let board = Board::from_string(&full_string);
+        // the pieces go in the cells on the board, in order
+        let mut pieces = vec!['.'; cells];
+        for (&i, piece) in on_board.iter().zip(string.chars()) {
+            pieces[i] = piece;
+        }
+        let pieces: String = pieces.into_iter().collect();
+        let mut full_string = format!("{}|{}|{}", width, height, pieces);
+        if wraps {
+            full_string.push_str("|wrap");
+        }
+        let board = Board::from_string(&full_string, topology).unwrap();
         if encountered_variants.contains(&board) {
             continue;
         }
@@ -143,7 +298,7 @@ pub fn generate_boards(
 }
 
 fn generate_with_prefix(
-    length: u8,
+    length: usize,
     prefix: &str,
     cow_count: u8,
     barn_count: u8,
@@ -151,12 +306,15 @@ fn generate_with_prefix(
     house_count: u8,
     empty_count: u8,
 ) -> Vec<String> {
-    if cow_count + barn_count + person_count + house_count + empty_count
-        > (length - prefix.len() as u8)
-    {
+    let piece_count = cow_count as usize
+        + barn_count as usize
+        + person_count as usize
+        + house_count as usize
+        + empty_count as usize;
+    if piece_count > length - prefix.len() {
         return Vec::new();
     }
-    if prefix.len() == length as usize {
+    if prefix.len() == length {
         return vec![prefix.to_string()];
     }
     let mut boards: Vec<String> = Vec::new();
@@ -243,7 +401,7 @@ fn random_sample<T>(v: Vec<T>, n: usize) -> Vec<T> {
     if v.len() <= n {
         return v;
     }
-    let mut sample : Vec<T> = Vec::new();
+    let mut sample: Vec<T> = Vec::new();
     let mut rng = rand::thread_rng();
     let mut v = v;
     for _ in 0..n {
@@ -252,4 +410,4 @@ fn random_sample<T>(v: Vec<T>, n: usize) -> Vec<T> {
     }
 
     sample
-}
\ No newline at end of file
+}
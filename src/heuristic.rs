@@ -0,0 +1,45 @@
+use crate::board::{Board, Piece, PLAIN_PLANES};
+
+// Lower bound on the number of moves still needed to solve a board. Informed searches are only
+// guaranteed to find a shortest solution when this never overestimates.
+// Estimates past 255 moves are cut to 255, which keeps them lower bounds.
+pub type Heuristic<const WORDS: usize = 1, const PLANES: usize = PLAIN_PLANES> =
+    fn(&Board<WORDS, PLANES>) -> u8;
+
+// every piece that still has to get home takes at least one move of its own
+pub fn movers_not_home<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+) -> u8 {
+    let mut count = 0;
+    for (piece, _) in Piece::HERDS {
+        if board.needs_home(piece) {
+            count += board.count_piece(piece);
+        }
+    }
+    count.min(u8::MAX as usize) as u8
+}
+
+// A piece that cannot slide straight into a goal of its kind needs at least two moves, one to get
+// onto a line with the goal and one to reach it. Empty cells and goals of other kinds never move,
+// so they block a line for good.
+pub fn line_distance<const WORDS: usize, const PLANES: usize>(board: &Board<WORDS, PLANES>) -> u8 {
+    let mut total: usize = 0;
+    for i in 0..board.cell_count() {
+        let piece = board.get_index(i);
+        if !board.needs_home(piece) {
+            continue;
+        }
+        let goal = piece.goal();
+        let on_line = board.topology.directions().iter().any(|&direction| {
+            board
+                .ray(i as u16, direction)
+                .map(|space| board.get_index(space as usize))
+                .take_while(|&piece| {
+                    piece != Piece::Empty && (!piece.is_goal() || Some(piece) == goal)
+                })
+                .any(|piece| Some(piece) == goal)
+        });
+        total += if on_line { 1 } else { 2 };
+    }
+    total.min(u8::MAX as usize) as u8
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::board::Board;
+use crate::solve::{shortest_solution_layers, Move};
+use crate::tablebase::Tablebase;
+
+pub enum Hint {
+    Solved,
+    // every move that starts a shortest solution, and how many moves those solutions take
+    Moves { moves: Vec<Move>, distance: u16 },
+    // no sequence of moves solves the board any more
+    Unsolvable,
+}
+
+// What to play next from `board`. A tablebase covering the board answers by lookup. Otherwise
+// `distances` is checked, a memo of exact distances keyed by canonical board that earlier calls
+// fill in, so following a hint makes the next one a lookup too; failing both, the board is searched.
+pub fn hint<const WORDS: usize>(
+    board: &Board<WORDS>,
+    distances: &mut HashMap<Board<WORDS>, u16>,
+    tablebase: Option<&Tablebase<WORDS>>,
+) -> Hint {
+    if board.is_solved() {
+        return Hint::Solved;
+    }
+
+    if let Some(tablebase) = tablebase.filter(|tablebase| tablebase.covers(board)) {
+        return match tablebase.distance(board) {
+            Some(distance) => {
+                moves_to_distance(board, distance, |next_board| tablebase.distance(next_board))
+            }
+            None => Hint::Unsolvable,
+        };
+    }
+
+    // A memo entry comes from the search below, which records every board on a shortest solution
+    // of the board searched. Every board one move closer to solved than one of those is on such a
+    // solution too, so it is in the memo as well.
+    if let Some(&distance) = distances.get(&board.canonical()) {
+        return moves_to_distance(board, distance, |next_board| {
+            distances.get(&next_board.canonical()).copied()
+        });
+    }
+
+    let layers = match shortest_solution_layers(*board) {
+        Some(layers) => layers,
+        None => return Hint::Unsolvable,
+    };
+    let distance = (layers.len() - 1) as u16;
+    for (depth, layer) in layers.iter().enumerate() {
+        for board in layer {
+            distances.insert(board.canonical(), distance - depth as u16);
+        }
+    }
+    moves_to_distance(board, distance, |next_board| {
+        layers[1].contains(next_board).then_some(distance - 1)
+    })
+}
+
+// the moves from `board` to a board `distance_of` puts one move closer to solved
+fn moves_to_distance<const WORDS: usize>(
+    board: &Board<WORDS>,
+    distance: u16,
+    distance_of: impl Fn(&Board<WORDS>) -> Option<u16>,
+) -> Hint {
+    let moves = board
+        .get_possible_moves()
+        .into_iter()
+        .filter(|(next_board, _, _, _)| distance_of(next_board) == Some(distance - 1))
+        .map(|(_, from, to, puller)| Move { from, to, puller })
+        .collect();
+    Hint::Moves { moves, distance }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{LargeBoard, Topology};
+
+    // 253 cows in one row and 3 in the other, each next to the last one's barn: 256 moves, one
+    // past what fits in a u8. Following the hint takes the next one from the memo.
+    #[test]
+    fn hints_count_past_255_moves() {
+        let s = format!("255|2|B{}_BOOO{}", "O".repeat(253), "_".repeat(251));
+        let board = LargeBoard::from_string(&s, Topology::Rect).unwrap();
+        let mut distances = HashMap::new();
+        let Hint::Moves { moves, distance } = hint(&board, &mut distances, None) else {
+            panic!("no moves for {}", board.to_tagged_string());
+        };
+        assert_eq!(distance, 256);
+        let next_board = board.apply(&moves[0]).unwrap();
+        assert!(matches!(
+            hint(&next_board, &mut distances, None),
+            Hint::Moves { distance: 255, .. }
+        ));
+    }
+}
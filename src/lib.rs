@@ -0,0 +1,24 @@
+//! Solver and puzzle generator for Make the Cows Come Home.
+//!
+//! Boards are parsed with [`Board::from_string`] or [`str::parse`], solved with the functions in
+//! [`solve`] and [`bidirectional`], generated from solved layouts with [`reverse_solver`] and
+//! [`generate`], and the generated files are curated into puzzle lists with [`combine`]. For a
+//! fixed layout, [`tablebase`] records the distance to solved of every board at once.
+
+pub mod bidirectional;
+pub mod board;
+pub mod combine;
+pub mod deadlock;
+pub mod generate;
+pub mod heuristic;
+pub mod hint;
+pub mod reverse_solver;
+pub mod solve;
+pub mod tablebase;
+
+pub use board::{
+    Board, CellSet, Direction, IllegalMove, LargeBoard, ParseBoardError, ParseBoardErrorKind,
+    Piece, Topology,
+};
+pub use reverse_solver::{reverse_solve, ReverseSolution};
+pub use solve::{verify_solution, Move, Solution, SolutionError};
@@ -2,100 +2,218 @@ use std::collections::{HashSet, VecDeque};
 
 use crate::{
     board::{Board, Piece},
-    solve::Move,
+    solve::{Move, Solution},
 };
 
 #[derive(Clone)]
 pub struct ReverseSolution {
     pub moves: Vec<Move>,
-    pub move_count: u8,
+    pub move_count: u16,
 }
 
-pub fn reverse_solve(
-    board: Board,
+impl ReverseSolution {
+    // the moves in the order they solve the puzzle
+    pub fn to_solution(&self) -> Solution {
+        Solution {
+            moves: self.moves.iter().rev().cloned().collect(),
+            move_count: self.move_count,
+            tree_size: 0,
+            can_be_solved: true,
+        }
+    }
+}
+
+// With use_symmetry, boards symmetric to one already queued are skipped, which shrinks the search
+// but also the iteration count reported for it.
+pub fn reverse_solve<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
     max_cow_count: u8,
     max_person_count: u8,
-) -> (Board, ReverseSolution, usize) {
-    let mut encountered_boards: HashSet<Board> = HashSet::new();
+    use_symmetry: bool,
+) -> (Board<WORDS, PLANES>, ReverseSolution, usize) {
+    let (mut puzzles, iterations) = reverse_solve_deepest(
+        board,
+        max_cow_count,
+        max_person_count,
+        use_symmetry,
+        1,
+        false,
+    );
+    let (puzzle_board, reverse_solution) = puzzles.pop().unwrap_or((
+        board,
+        ReverseSolution {
+            moves: Vec::new(),
+            move_count: 0,
+        },
+    ));
+    (puzzle_board, reverse_solution, iterations)
+}
 
-    let mut board_queue: VecDeque<(Board, ReverseSolution)> = VecDeque::new();
+// Like reverse_solve, but keeps the count deepest boards the search reaches, deepest first, so one
+// search can seed several puzzles. No two of them are symmetric variants of each other; with
+// elegant_only, boards whose solution is not elegant are passed over. Among boards of the same
+// depth, the first ones reached are kept.
+//
+// Only the frontier holds boards. Every board reached gets an entry in a flat list of parent
+// links, and moves are traced back through it only for boards that could make the cut.
+pub fn reverse_solve_deepest<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    max_cow_count: u8,
+    max_person_count: u8,
+    use_symmetry: bool,
+    count: usize,
+    elegant_only: bool,
+) -> (Vec<(Board<WORDS, PLANES>, ReverseSolution)>, usize) {
+    let mut encountered_boards: HashSet<Board<WORDS, PLANES>> = HashSet::new();
+    // the link to the board each board was reached from, indexed by the order boards are reached
+    let mut parents: Vec<Option<(u32, Move)>> = Vec::new();
+    // boards still to expand, with their index into parents and their depth
+    let mut board_queue: VecDeque<(Board<WORDS, PLANES>, u32, u16)> = VecDeque::new();
+    // the deepest boards so far with their canonical forms, shallowest first; the queue is in order
+    // of depth, so boards only ever join at the back
+    let mut deepest: VecDeque<(Board<WORDS, PLANES>, Board<WORDS, PLANES>, ReverseSolution)> =
+        VecDeque::new();
 
     if max_person_count > 0 && board.count_piece(Piece::House) == 0 {
-        choose_people_locations(&board, max_person_count, &mut board_queue);
+        choose_people_locations(&board, max_person_count, &mut parents, &mut board_queue);
     }
 
-    let mut most_moves = 0;
-    let mut board_with_most_moves = board.clone();
-    let mut best_reverse_solution = ReverseSolution {
-        moves: Vec::new(),
-        move_count: 0,
-    };
-
-    board_queue.push_back((board_with_most_moves.clone(), best_reverse_solution.clone()));
-    encountered_boards.insert(board.clone());
+    board_queue.push_back((board, parents.len() as u32, 0));
+    parents.push(None);
+    encountered_boards.insert(board.search_key(use_symmetry));
 
     let mut iterations = 0;
 
-    while board_queue.len() > 0 {
+    while let Some((board, id, move_count)) = board_queue.pop_front() {
         iterations += 1;
         if iterations % 100000 == 0 {
-            println!("Iterations: {}. In board queue: {}", iterations, board_queue.len());
+            println!(
+                "Iterations: {}. In board queue: {}",
+                iterations,
+                board_queue.len()
+            );
         }
-        let next_item = board_queue.pop_front().unwrap();
-        let board = next_item.0;
-        let reverse_solution = next_item.1;
         let previous_boards = board.get_possible_previous_boards();
 
         for (board, from, to, puller) in previous_boards {
-            if encountered_boards.contains(&board) {
+            let key = board.search_key(use_symmetry);
+            if encountered_boards.contains(&key) {
                 continue;
             }
-            if board.count_piece(Piece::Cow) > max_cow_count
-                || board.count_piece(Piece::Person) > max_person_count
+            if board.count_cows() > max_cow_count as usize
+                || board.count_piece(Piece::Person) > max_person_count as usize
             {
                 continue;
             }
-            let mut new_moves = reverse_solution.moves.clone();
-            new_moves.push(Move { from, to, puller });
-            let new_solution = ReverseSolution {
-                moves: new_moves,
-                move_count: reverse_solution.move_count + 1,
-            };
-            if new_solution.move_count > most_moves {
-                most_moves = new_solution.move_count;
-                board_with_most_moves = board.clone();
-                best_reverse_solution = new_solution.clone();
+            let next_id = parents.len() as u32;
+            parents.push(Some((id, Move { from, to, puller })));
+            encountered_boards.insert(key);
+            board_queue.push_back((board, next_id, move_count + 1));
+
+            if count > 0 && (deepest.len() < count || deepest[0].2.move_count <= move_count) {
+                consider_puzzle(
+                    &mut deepest,
+                    &parents,
+                    board,
+                    next_id,
+                    move_count + 1,
+                    count,
+                    elegant_only,
+                );
             }
-            encountered_boards.insert(board.clone());
-            board_queue.push_back((board, new_solution));
         }
     }
 
-    return (board_with_most_moves, best_reverse_solution, iterations);
+    let puzzles = deepest
+        .into_iter()
+        .rev()
+        .map(|(board, _, reverse_solution)| (board, reverse_solution))
+        .collect();
+    (puzzles, iterations)
 }
 
-fn choose_people_locations(board: &Board, max_person_count: u8, board_queue: &mut VecDeque<(Board, ReverseSolution)>) {
-    let mut blank_indices : Vec<u8> = Vec::new();
-    for i in 0..(board.width * board.height) {
-        if board.pieces[i as usize] == Piece::Blank {
-            blank_indices.push(i);
+// Adds board to deepest if it is deep enough and passes the filters, dropping the shallowest
+// board when there are more than count.
+fn consider_puzzle<const WORDS: usize, const PLANES: usize>(
+    deepest: &mut VecDeque<(Board<WORDS, PLANES>, Board<WORDS, PLANES>, ReverseSolution)>,
+    parents: &[Option<(u32, Move)>],
+    board: Board<WORDS, PLANES>,
+    id: u32,
+    move_count: u16,
+    count: usize,
+    elegant_only: bool,
+) {
+    let canonical = board.canonical();
+    let variant_position = deepest.iter().position(|(_, other, _)| *other == canonical);
+    if let Some(position) = variant_position {
+        if deepest[position].2.move_count >= move_count {
+            return;
+        }
+    }
+
+    // traced from the board back to the solved one, then put in the order they were undone
+    let mut moves = Vec::new();
+    let mut id = id;
+    while let Some((parent, m)) = &parents[id as usize] {
+        moves.push(m.clone());
+        id = *parent;
+    }
+    moves.reverse();
+
+    let reverse_solution = ReverseSolution { moves, move_count };
+    if elegant_only && !reverse_solution.to_solution().is_elegant(&board) {
+        return;
+    }
+
+    if let Some(position) = variant_position {
+        deepest.remove(position);
+    }
+    deepest.push_back((board, canonical, reverse_solution));
+    if deepest.len() > count {
+        deepest.pop_front();
+    }
+}
+
+fn choose_people_locations<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+    max_person_count: u8,
+    parents: &mut Vec<Option<(u32, Move)>>,
+    board_queue: &mut VecDeque<(Board<WORDS, PLANES>, u32, u16)>,
+) {
+    let mut blank_indices: Vec<u16> = Vec::new();
+    for i in 0..board.cell_count() {
+        if board.get_index(i) == Piece::Blank {
+            blank_indices.push(i as u16);
         }
     }
 
     // get all unique combinations of blank indices with length max_person_count
-    let mut combinations : Vec<Vec<u8>> = Vec::new();
-    get_combinations(&mut combinations, &blank_indices, max_person_count as usize, 0, Vec::new());
+    let mut combinations: Vec<Vec<u16>> = Vec::new();
+    get_combinations(
+        &mut combinations,
+        &blank_indices,
+        max_person_count as usize,
+        0,
+        Vec::new(),
+    );
 
     for combination in combinations {
-        let mut new_board = board.clone();
+        let mut new_board = *board;
         for index in combination {
-            new_board.pieces[index as usize] = Piece::Person;
+            new_board.set_index(index as usize, Piece::Person);
         }
-        board_queue.push_back((new_board, ReverseSolution { moves: Vec::new(), move_count: 0 }));
+        board_queue.push_back((new_board, parents.len() as u32, 0));
+        parents.push(None);
     }
 }
 
-fn get_combinations(combinations: &mut Vec<Vec<u8>>, blank_indices: &Vec<u8>, max_count: usize, index: usize, current_combination: Vec<u8>) {
+pub(crate) fn get_combinations(
+    combinations: &mut Vec<Vec<u16>>,
+    blank_indices: &Vec<u16>,
+    max_count: usize,
+    index: usize,
+    current_combination: Vec<u16>,
+) {
     if current_combination.len() == max_count {
         combinations.push(current_combination);
         return;
@@ -105,6 +223,18 @@ fn get_combinations(combinations: &mut Vec<Vec<u8>>, blank_indices: &Vec<u8>, ma
     }
     let mut new_combination = current_combination.clone();
     new_combination.push(blank_indices[index]);
-    get_combinations(combinations, blank_indices, max_count, index + 1, new_combination);
-    get_combinations(combinations, blank_indices, max_count, index + 1, current_combination);
-}
\ No newline at end of file
+    get_combinations(
+        combinations,
+        blank_indices,
+        max_count,
+        index + 1,
+        new_combination,
+    );
+    get_combinations(
+        combinations,
+        blank_indices,
+        max_count,
+        index + 1,
+        current_combination,
+    );
+}
@@ -1 +0,0 @@
-pub const HEXAGONAL_MODE: bool = false;
@@ -1,131 +1,804 @@
-use std::collections::{HashSet, HashMap};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
 
-use crate::board::{Board, Piece};
+use crate::board::{Board, Piece, PLAIN_PLANES};
+use crate::deadlock::DeadlockDetector;
+use crate::heuristic::Heuristic;
 
 #[derive(Clone, Debug)]
 pub struct Move {
-    pub from: u8,
-    pub to: u8,
-    pub puller: u8
+    pub from: u16,
+    pub to: u16,
+    pub puller: u16,
 }
 
 #[derive(Clone)]
 pub struct Solution {
     pub moves: Vec<Move>,
-    pub move_count: u8,
+    pub move_count: u16,
     pub tree_size: usize,
-    pub can_be_solved: bool
+    pub can_be_solved: bool,
 }
 
-pub fn solve(board : Board, solution_map : &mut HashMap<Board, Solution>) -> Solution {
+pub fn solve<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    solution_map: &mut HashMap<Board<WORDS, PLANES>, Solution>,
+) -> Solution {
     let mut encountered_boards = HashSet::new();
-    solve_internal(board, solution_map, &mut encountered_boards)
+    let deadlocks = DeadlockDetector::new(&board);
+    solve_internal(board, solution_map, &mut encountered_boards, &deadlocks)
 }
 
-fn solve_internal(board : Board, solution_map : &mut HashMap<Board, Solution>, encountered_boards : &mut HashSet<Board>) -> Solution {
-    
+// Breadth-first search, so the first solved board found is reached in the fewest possible moves.
+// Unlike solve, the result does not depend on the order boards are visited in. With use_symmetry,
+// boards that are rotations, reflections or cow/person swaps of one already seen are skipped.
+// Boards the deadlock detector rejects are never queued.
+pub fn solve_shortest<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    use_symmetry: bool,
+) -> Solution {
+    if board.is_solved() {
+        return Solution {
+            moves: Vec::new(),
+            move_count: 0,
+            tree_size: 1,
+            can_be_solved: true,
+        };
+    }
+    let deadlocks = DeadlockDetector::new(&board);
+    if deadlocks.is_dead(&board) {
+        return Solution {
+            moves: Vec::new(),
+            move_count: 0,
+            tree_size: 1,
+            can_be_solved: false,
+        };
+    }
+
+    // search key of every board seen so far -> the board itself, and the key of the board and the
+    // move it was first reached from
+    let mut parents: Parents<WORDS, PLANES> = HashMap::new();
+    let mut board_queue: VecDeque<(Board<WORDS, PLANES>, Board<WORDS, PLANES>)> = VecDeque::new();
+    let key = board.search_key(use_symmetry);
+    parents.insert(key, (board, None));
+    board_queue.push_back((board, key));
+
+    while let Some((board, key)) = board_queue.pop_front() {
+        for (next_board, from, to, puller) in board.get_possible_moves() {
+            let next_key = next_board.search_key(use_symmetry);
+            if parents.contains_key(&next_key) || deadlocks.is_dead(&next_board) {
+                continue;
+            }
+            parents.insert(
+                next_key,
+                (next_board, Some((key, Move { from, to, puller }))),
+            );
+            if next_board.is_solved() {
+                let moves = trace_moves(&parents, &next_key);
+                return Solution {
+                    move_count: moves.len() as u16,
+                    moves,
+                    tree_size: parents.len(),
+                    can_be_solved: true,
+                };
+            }
+            board_queue.push_back((next_board, next_key));
+        }
+    }
+
+    Solution {
+        moves: Vec::new(),
+        move_count: 0,
+        tree_size: parents.len(),
+        can_be_solved: false,
+    }
+}
+
+type Parents<const WORDS: usize, const PLANES: usize> =
+    HashMap<Board<WORDS, PLANES>, (Board<WORDS, PLANES>, Option<(Board<WORDS, PLANES>, Move)>)>;
+
+// follows parent links back from `key` to the start board and returns the moves in play order
+fn trace_moves<const WORDS: usize, const PLANES: usize>(
+    parents: &Parents<WORDS, PLANES>,
+    key: &Board<WORDS, PLANES>,
+) -> Vec<Move> {
+    let mut moves = Vec::new();
+    let mut key = key;
+    while let Some((_, Some((parent_key, m)))) = parents.get(key) {
+        moves.push(m.clone());
+        key = parent_key;
+    }
+    moves.reverse();
+    moves
+}
+
+// Replays `moves` from `board`, checking each one can be made and that they leave the board solved.
+pub fn verify_solution<const WORDS: usize, const PLANES: usize>(
+    board: &Board<WORDS, PLANES>,
+    moves: &[Move],
+) -> Result<(), SolutionError> {
+    let mut board = *board;
+    for (index, m) in moves.iter().enumerate() {
+        board = board
+            .apply(m)
+            .map_err(|_| SolutionError::IllegalMove(index))?;
+    }
+    if !board.is_solved() {
+        return Err(SolutionError::NotSolved);
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolutionError {
+    // index of the first move that can't be made
+    IllegalMove(usize),
+    // every move can be made, but the board isn't solved after the last one
+    NotSolved,
+}
+
+impl fmt::Display for SolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolutionError::IllegalMove(index) => write!(f, "move {} can't be made", index + 1),
+            SolutionError::NotSolved => write!(f, "the board isn't solved after the last move"),
+        }
+    }
+}
+
+impl std::error::Error for SolutionError {}
+
+// The boards some shortest solution of `board` passes through, by the number of moves taken to
+// reach them: the first layer holds just `board` and the last the solved boards. Symmetric boards
+// are kept apart, so every move of every shortest solution leads from one layer into the next.
+// None if `board` can't be solved.
+pub(crate) fn shortest_solution_layers<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+) -> Option<Vec<HashSet<Board<WORDS, PLANES>>>> {
+    let deadlocks = DeadlockDetector::new(&board);
+    if deadlocks.is_dead(&board) {
+        return None;
+    }
+    let mut encountered_boards: HashSet<Board<WORDS, PLANES>> = HashSet::new();
+    encountered_boards.insert(board);
+    let mut layers: Vec<HashSet<Board<WORDS, PLANES>>> = vec![HashSet::from([board])];
+
+    while !layers[layers.len() - 1]
+        .iter()
+        .any(|board| board.is_solved())
+    {
+        let mut next_layer = HashSet::new();
+        for board in &layers[layers.len() - 1] {
+            for (next_board, _, _, _) in board.get_possible_moves() {
+                if !deadlocks.is_dead(&next_board) && encountered_boards.insert(next_board) {
+                    next_layer.insert(next_board);
+                }
+            }
+        }
+        if next_layer.is_empty() {
+            return None;
+        }
+        layers.push(next_layer);
+    }
+
+    // working back from the solved boards, drop every board with no move into the layer after it
+    let last = layers.len() - 1;
+    layers[last].retain(|board| board.is_solved());
+    for depth in (0..last).rev() {
+        let (earlier, later) = layers.split_at_mut(depth + 1);
+        earlier[depth].retain(|board| {
+            board
+                .get_possible_moves()
+                .iter()
+                .any(|(next_board, _, _, _)| later[0].contains(next_board))
+        });
+    }
+    Some(layers)
+}
+
+// Number of distinct move sequences that solve `board` in the fewest moves, 0 if it can't be
+// solved. Saturates at u64::MAX.
+pub fn count_optimal_solutions<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+) -> u64 {
+    match shortest_solution_layers(board) {
+        Some(layers) => count_layer_solutions(&layers),
+        None => 0,
+    }
+}
+
+// count_optimal_solutions for the board layers were found from
+pub(crate) fn count_layer_solutions<const WORDS: usize, const PLANES: usize>(
+    layers: &[HashSet<Board<WORDS, PLANES>>],
+) -> u64 {
+    // solutions from each board of the layer after the one being counted
+    let mut counts: HashMap<Board<WORDS, PLANES>, u64> = layers[layers.len() - 1]
+        .iter()
+        .map(|board| (*board, 1))
+        .collect();
+    for layer in layers.iter().rev().skip(1) {
+        let mut layer_counts = HashMap::new();
+        for board in layer {
+            let mut count: u64 = 0;
+            for (next_board, _, _, _) in board.get_possible_moves() {
+                if let Some(next_count) = counts.get(&next_board) {
+                    count = count.saturating_add(*next_count);
+                }
+            }
+            layer_counts.insert(*board, count);
+        }
+        counts = layer_counts;
+    }
+    counts.values().sum()
+}
+
+// Every shortest solution of `board`, each as its moves in play order. Nothing if it can't be
+// solved, and a single empty solution if it already is.
+pub fn optimal_solutions<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+) -> OptimalSolutions<WORDS, PLANES> {
+    let layers = shortest_solution_layers(board).unwrap_or_default();
+    let mut solutions = OptimalSolutions {
+        layers,
+        choices: Vec::new(),
+        path: Vec::new(),
+        already_solved: false,
+    };
+    match solutions.layers.len() {
+        0 => {}
+        1 => solutions.already_solved = true,
+        _ => solutions.choices.push(solutions.next_steps(&board, 0)),
+    }
+    solutions
+}
+
+// Depth first walk through the boards on shortest solutions, one solution per step.
+pub struct OptimalSolutions<const WORDS: usize = 1, const PLANES: usize = PLAIN_PLANES> {
+    layers: Vec<HashSet<Board<WORDS, PLANES>>>,
+    // for each move of the current path, the choices at that point not yet taken
+    choices: Vec<Vec<(Board<WORDS, PLANES>, Move)>>,
+    path: Vec<Move>,
+    already_solved: bool,
+}
+
+impl<const WORDS: usize, const PLANES: usize> OptimalSolutions<WORDS, PLANES> {
+    // moves from `board`, `depth` moves into a solution, that stay on a shortest one
+    fn next_steps(
+        &self,
+        board: &Board<WORDS, PLANES>,
+        depth: usize,
+    ) -> Vec<(Board<WORDS, PLANES>, Move)> {
+        let mut steps: Vec<(Board<WORDS, PLANES>, Move)> = board
+            .get_possible_moves()
+            .into_iter()
+            .filter(|(next_board, _, _, _)| self.layers[depth + 1].contains(next_board))
+            .map(|(next_board, from, to, puller)| (next_board, Move { from, to, puller }))
+            .collect();
+        // taken from the back
+        steps.reverse();
+        steps
+    }
+}
+
+impl<const WORDS: usize, const PLANES: usize> Iterator for OptimalSolutions<WORDS, PLANES> {
+    type Item = Vec<Move>;
+
+    fn next(&mut self) -> Option<Vec<Move>> {
+        if self.already_solved {
+            self.already_solved = false;
+            return Some(Vec::new());
+        }
+        let move_count = self.layers.len().saturating_sub(1);
+        while let Some(choices) = self.choices.last_mut() {
+            match choices.pop() {
+                Some((next_board, m)) => {
+                    self.path.push(m);
+                    if self.path.len() == move_count {
+                        let solution = self.path.clone();
+                        self.path.pop();
+                        return Some(solution);
+                    }
+                    let steps = self.next_steps(&next_board, self.path.len());
+                    self.choices.push(steps);
+                }
+                None => {
+                    self.choices.pop();
+                    self.path.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+// A* search guided by `heuristic`. Returns a shortest solution as long as the heuristic never
+// overestimates; tree_size is the number of boards expanded, for comparing heuristics.
+pub fn solve_astar<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    heuristic: Heuristic<WORDS, PLANES>,
+    use_symmetry: bool,
+) -> Solution {
+    let deadlocks = DeadlockDetector::new(&board);
+    // boards are numbered in the order they are first reached
+    let mut boards: Vec<Board<WORDS, PLANES>> = Vec::new();
+    let mut parents: Vec<Option<(usize, Move)>> = Vec::new();
+    let mut best_lengths: HashMap<Board<WORDS, PLANES>, u16> = HashMap::new();
+    // ordered by estimated total length, then deepest first
+    let mut open: BinaryHeap<Reverse<(u16, Reverse<u16>, usize)>> = BinaryHeap::new();
+
+    best_lengths.insert(board.search_key(use_symmetry), 0);
+    open.push(Reverse((u16::from(heuristic(&board)), Reverse(0), 0)));
+    boards.push(board);
+    parents.push(None);
+
+    let mut expanded = 0;
+    while let Some(Reverse((_, Reverse(length), id))) = open.pop() {
+        let board = boards[id];
+        if best_lengths[&board.search_key(use_symmetry)] < length {
+            // a shorter way to this board was found after this entry was queued
+            continue;
+        }
+        expanded += 1;
+        if board.is_solved() {
+            let mut moves = Vec::new();
+            let mut id = id;
+            while let Some((parent, m)) = &parents[id] {
+                moves.push(m.clone());
+                id = *parent;
+            }
+            moves.reverse();
+            return Solution {
+                moves,
+                move_count: length,
+                tree_size: expanded,
+                can_be_solved: true,
+            };
+        }
+
+        for (next_board, from, to, puller) in board.get_possible_moves() {
+            if deadlocks.is_dead(&next_board) {
+                continue;
+            }
+            let next_length = length + 1;
+            let next_key = next_board.search_key(use_symmetry);
+            if let Some(&best_length) = best_lengths.get(&next_key) {
+                if best_length <= next_length {
+                    continue;
+                }
+            }
+            let next_id = boards.len();
+            let estimate = next_length + u16::from(heuristic(&next_board));
+            best_lengths.insert(next_key, next_length);
+            boards.push(next_board);
+            parents.push(Some((id, Move { from, to, puller })));
+            open.push(Reverse((estimate, Reverse(next_length), next_id)));
+        }
+    }
+
+    Solution {
+        moves: Vec::new(),
+        move_count: 0,
+        tree_size: expanded,
+        can_be_solved: false,
+    }
+}
+
+// Iterative deepening A*: repeated depth-first searches with a growing bound on the estimated
+// solution length. Each search keeps the fewest moves it has reached every board in, and doesn't
+// go on from a board it has already reached in as few, so it never re-expands one through a
+// longer path. With use_symmetry, boards are looked up by their canonical form. tree_size counts
+// every expansion over all iterations.
+pub fn solve_ida_star<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    heuristic: Heuristic<WORDS, PLANES>,
+    use_symmetry: bool,
+) -> Solution {
+    let mut bound = u16::from(heuristic(&board));
+    let mut path: Vec<Move> = Vec::new();
+    let mut best_lengths: HashMap<Board<WORDS, PLANES>, u16> = HashMap::new();
+    let mut expanded = 0;
+    let deadlocks = DeadlockDetector::new(&board);
+    let unsolvable = |expanded| Solution {
+        moves: Vec::new(),
+        move_count: 0,
+        tree_size: expanded,
+        can_be_solved: false,
+    };
+    if deadlocks.is_dead(&board) {
+        return unsolvable(1);
+    }
+
+    let mut reached = 0;
+    loop {
+        best_lengths.clear();
+        let search = IdaStarSearch {
+            bound,
+            heuristic,
+            deadlocks: &deadlocks,
+            use_symmetry,
+        };
+        match search.run(&board, 0, &mut path, &mut best_lengths, &mut expanded) {
+            None => {
+                return Solution {
+                    move_count: path.len() as u16,
+                    moves: path,
+                    tree_size: expanded,
+                    can_be_solved: true,
+                };
+            }
+            // with nothing over the bound, or no boards the last search didn't reach, a higher
+            // bound can't find a solution either
+            Some(next_bound) if next_bound != u16::MAX && best_lengths.len() > reached => {
+                bound = next_bound;
+                reached = best_lengths.len();
+            }
+            Some(_) => return unsolvable(expanded),
+        }
+    }
+}
+
+// what stays the same over one of solve_ida_star's depth-first searches
+struct IdaStarSearch<'a, const WORDS: usize, const PLANES: usize> {
+    bound: u16,
+    heuristic: Heuristic<WORDS, PLANES>,
+    deadlocks: &'a DeadlockDetector<WORDS>,
+    use_symmetry: bool,
+}
+
+impl<const WORDS: usize, const PLANES: usize> IdaStarSearch<'_, WORDS, PLANES> {
+    // Returns None once a solution is on `path`, otherwise the smallest estimate that went over
+    // the bound (u16::MAX if nothing did, meaning the board cannot be solved).
+    fn run(
+        &self,
+        board: &Board<WORDS, PLANES>,
+        length: u16,
+        path: &mut Vec<Move>,
+        best_lengths: &mut HashMap<Board<WORDS, PLANES>, u16>,
+        expanded: &mut usize,
+    ) -> Option<u16> {
+        let estimate = length + u16::from((self.heuristic)(board));
+        if estimate > self.bound {
+            return Some(estimate);
+        }
+        best_lengths.insert(board.search_key(self.use_symmetry), length);
+        *expanded += 1;
+        if board.is_solved() {
+            return None;
+        }
+
+        let mut next_bound = u16::MAX;
+        for (next_board, from, to, puller) in board.get_possible_moves() {
+            let next_length = length + 1;
+            let next_key = next_board.search_key(self.use_symmetry);
+            // also skips boards on the path, which were all reached in fewer moves
+            if best_lengths
+                .get(&next_key)
+                .is_some_and(|&best| best <= next_length)
+                || self.deadlocks.is_dead(&next_board)
+            {
+                continue;
+            }
+            path.push(Move { from, to, puller });
+            match self.run(&next_board, next_length, path, best_lengths, expanded) {
+                None => return None,
+                Some(over) => next_bound = next_bound.min(over),
+            }
+            path.pop();
+        }
+        Some(next_bound)
+    }
+}
+
+fn solve_internal<const WORDS: usize, const PLANES: usize>(
+    board: Board<WORDS, PLANES>,
+    solution_map: &mut HashMap<Board<WORDS, PLANES>, Solution>,
+    encountered_boards: &mut HashSet<Board<WORDS, PLANES>>,
+    deadlocks: &DeadlockDetector<WORDS>,
+) -> Solution {
     if board.is_solved() {
         let solution = Solution {
             moves: Vec::new(),
             move_count: 0,
             tree_size: 1,
-            can_be_solved: true
+            can_be_solved: true,
         };
         solution_map.insert(board, solution.clone());
         return solution;
     }
 
-    if solution_map.contains_key(&board) {
-        return solution_map.get(&board).unwrap().clone();
+    // a board searched before adds nothing to the count, or every path to it would count it again
+    if let Some(solution) = solution_map.get(&board) {
+        return Solution {
+            tree_size: 0,
+            ..solution.clone()
+        };
     }
-   
 
-    encountered_boards.insert(board.clone());
+    encountered_boards.insert(board);
 
     let mut tree_size = 1;
     let mut can_be_solved = false;
-    let mut best_moves : Vec<Move> = Vec::new();
-    let mut best_moves_length : u8 = 120;
+    let mut best_moves: Vec<Move> = Vec::new();
+    let mut best_moves_length: u16 = u16::MAX;
 
     let possible_board_moves = board.get_possible_moves();
     for (possible_board, from, to, puller) in possible_board_moves {
-        if encountered_boards.contains(&possible_board) {
+        if encountered_boards.contains(&possible_board) || deadlocks.is_dead(&possible_board) {
             continue;
         }
-        let solution = solve_internal(possible_board.clone(), solution_map, encountered_boards);
+        let solution = solve_internal(possible_board, solution_map, encountered_boards, deadlocks);
 
         if !solution.can_be_solved {
             continue;
-        }      
+        }
 
         tree_size += solution.tree_size;
-        
-        let solution_len = solution.moves.len() as u8;
+
+        let solution_len = solution.moves.len() as u16;
 
         can_be_solved = true;
         if solution_len < best_moves_length {
             best_moves_length = solution_len + 1;
             best_moves = solution.moves.clone();
-            best_moves.insert(0, Move {
-                from: from,
-                to: to,
-                puller: puller
-            });
+            best_moves.insert(0, Move { from, to, puller });
         }
     }
 
     encountered_boards.remove(&board);
 
-    let best_solution = Solution { moves: best_moves, move_count: best_moves_length, tree_size: tree_size, can_be_solved: can_be_solved };
+    let best_solution = Solution {
+        moves: best_moves,
+        move_count: best_moves_length,
+        tree_size,
+        can_be_solved,
+    };
     solution_map.insert(board, best_solution.clone());
-    return best_solution;
+    best_solution
 }
 
 impl Solution {
-    pub fn uses_all_pieces(&self, board: &Board) -> bool {
-        let mut encountered_indices : HashSet<u8> = HashSet::new();
+    pub fn uses_all_pieces<const WORDS: usize, const PLANES: usize>(
+        &self,
+        board: &Board<WORDS, PLANES>,
+    ) -> bool {
+        let mut encountered_indices: HashSet<u16> = HashSet::new();
         for m in &self.moves {
             encountered_indices.insert(m.from);
             encountered_indices.insert(m.to);
             encountered_indices.insert(m.puller);
         }
-        for i in 0..board.pieces.len() {
-            let piece = board.pieces[i];
-            if piece == Piece::Empty || piece == Piece::Blank {
+        for i in 0..board.cell_count() {
+            let piece = board.get_index(i);
+            if piece == Piece::Empty || piece == Piece::Blank || piece == Piece::Outside {
                 continue;
             }
-            if !encountered_indices.contains(&(i as u8)) {
+            if !encountered_indices.contains(&(i as u16)) {
                 return false;
             }
         }
-        return true;
+        true
     }
 
-    pub fn uses_all_rows_columns(&self, board: &Board) -> bool {
-        let mut encountered_rows : HashSet<u8> = HashSet::new();
-        let mut encountered_columns : HashSet<u8> = HashSet::new();
+    // only rows and columns with cells on the board count, for boards that aren't rectangles
+    pub fn uses_all_rows_columns<const WORDS: usize, const PLANES: usize>(
+        &self,
+        board: &Board<WORDS, PLANES>,
+    ) -> bool {
+        let mut encountered_rows: HashSet<u16> = HashSet::new();
+        let mut encountered_columns: HashSet<u16> = HashSet::new();
+        let width = board.width as u16;
         for m in &self.moves {
-            encountered_rows.insert(m.from / board.width);
-            encountered_rows.insert(m.to / board.width);
-            encountered_rows.insert(m.puller / board.width);
-            encountered_columns.insert(m.from % board.width);
-            encountered_columns.insert(m.to % board.width);
-            encountered_columns.insert(m.puller % board.width);
-        }
-        for i in 0..board.height {
-            if !encountered_rows.contains(&i) {
-                return false;
+            encountered_rows.insert(m.from / width);
+            encountered_rows.insert(m.to / width);
+            encountered_rows.insert(m.puller / width);
+            encountered_columns.insert(m.from % width);
+            encountered_columns.insert(m.to % width);
+            encountered_columns.insert(m.puller % width);
+        }
+        let outside = board.piece_mask(Piece::Outside);
+        (0..board.cell_count() as u16).all(|i| {
+            outside.contains(i)
+                || (encountered_rows.contains(&(i / width))
+                    && encountered_columns.contains(&(i % width)))
+        })
+    }
+
+    pub fn is_elegant<const WORDS: usize, const PLANES: usize>(
+        &self,
+        board: &Board<WORDS, PLANES>,
+    ) -> bool {
+        self.uses_all_pieces(board) && self.uses_all_rows_columns(board)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bidirectional::solve_bidirectional;
+    use crate::board::{
+        tests::{corpus_files, corpus_rows},
+        ColouredBoard, LargeBoard, Topology,
+    };
+    use crate::heuristic::{line_distance, movers_not_home};
+    use crate::reverse_solver::reverse_solve_deepest;
+
+    fn check_corpus(directory: &str, topology: Topology) {
+        for (board, recorded) in corpus_rows(directory) {
+            let solution =
+                solve_shortest(Board::<1>::from_string(&board, topology).unwrap(), false);
+            assert!(solution.can_be_solved, "{} cannot be solved", board);
+            assert_eq!(solution.move_count.to_string(), recorded, "{}", board);
+        }
+    }
+
+    #[test]
+    fn shortest_solutions_match_rect_corpus() {
+        check_corpus("rect", Topology::Rect);
+    }
+
+    #[test]
+    fn shortest_solutions_match_hex_corpus() {
+        check_corpus("hex", Topology::Hex);
+    }
+
+    // Every corpus row, not just a sample: solved, replayed and checked against its recorded move
+    // count. Takes minutes, so it only runs when asked for, with cargo test --release -- --ignored.
+    #[test]
+    #[ignore]
+    fn every_corpus_row_solves_and_replays() {
+        for (directory, topology) in [("rect", Topology::Rect), ("hex", Topology::Hex)] {
+            for (text, recorded) in corpus_files(directory).into_iter().flatten() {
+                let board = Board::<1>::from_string(&text, topology).unwrap();
+                let solution = solve_shortest(board, false);
+                assert!(solution.can_be_solved, "{} cannot be solved", text);
+                assert_eq!(solution.move_count.to_string(), recorded, "{}", text);
+                assert_eq!(verify_solution(&board, &solution.moves), Ok(()), "{}", text);
             }
         }
-        for i in 0..board.width {
-            if !encountered_columns.contains(&i) {
-                return false;
+    }
+
+    fn corpus_boards() -> Vec<Board> {
+        let mut boards = Vec::new();
+        for (directory, topology) in [("rect", Topology::Rect), ("hex", Topology::Hex)] {
+            for (board, _) in corpus_rows(directory) {
+                boards.push(Board::from_string(&board, topology).unwrap());
             }
         }
-        return true;
+        boards
     }
 
-    pub fn is_elegant(&self, board: &Board) -> bool {
-        self.uses_all_pieces(&board) && self.uses_all_rows_columns(&board)
+    // boards of up to 12 cells, which even the depth first search gets through quickly
+    #[test]
+    fn solvers_give_solutions_that_replay() {
+        for board in corpus_boards()
+            .into_iter()
+            .filter(|board| board.cell_count() <= 12)
+        {
+            let solutions = [
+                ("solve", solve(board, &mut HashMap::new())),
+                ("solve_shortest", solve_shortest(board, true)),
+                ("solve_astar", solve_astar(board, movers_not_home, false)),
+                ("solve_bidirectional", solve_bidirectional(board)),
+            ];
+            for (name, solution) in solutions {
+                assert!(
+                    solution.can_be_solved,
+                    "{} can't solve {}",
+                    name,
+                    board.to_tagged_string()
+                );
+                assert_eq!(solution.moves.len(), solution.move_count as usize);
+                assert_eq!(
+                    verify_solution(&board, &solution.moves),
+                    Ok(()),
+                    "{} on {}",
+                    name,
+                    board.to_tagged_string()
+                );
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reverse_solutions_replay() {
+        for (layout, cows, people) in [("4|3|B____E____H_", 2, 2), ("5|3|_B_____B______E", 3, 2)] {
+            let layout = Board::<1>::from_string(layout, Topology::Rect).unwrap();
+            let (puzzles, _) = reverse_solve_deepest(layout, cows, people, false, 4, false);
+            assert!(!puzzles.is_empty());
+            for (board, reverse_solution) in puzzles {
+                assert_eq!(
+                    verify_solution(&board, &reverse_solution.to_solution().moves),
+                    Ok(())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn broken_solutions_are_rejected() {
+        let board = Board::<1>::from_string("4|3|B_PO____O__P", Topology::Rect).unwrap();
+        let moves = solve_shortest(board, false).moves;
+        assert!(moves.len() > 2);
+        assert_eq!(
+            verify_solution(&board, &moves[..moves.len() - 1]),
+            Err(SolutionError::NotSolved)
+        );
+        assert_eq!(verify_solution(&board, &[]), Err(SolutionError::NotSolved));
+
+        // a move made twice finds no piece to move the second time
+        let mut repeated = moves.clone();
+        repeated.insert(1, moves[0].clone());
+        assert_eq!(
+            verify_solution(&board, &repeated),
+            Err(SolutionError::IllegalMove(1))
+        );
+
+        let mut off_the_board = moves.clone();
+        off_the_board[2].from = 99;
+        assert_eq!(
+            verify_solution(&board, &off_the_board),
+            Err(SolutionError::IllegalMove(2))
+        );
+    }
+
+    // Five people with no house who could end up on almost any of 100 cells: far too many solved
+    // boards to search backwards from.
+    #[test]
+    fn bidirectional_search_with_many_solved_boards() {
+        let s = format!("10|10|BOP{}PPPP", "_".repeat(93));
+        let board = LargeBoard::from_string(&s, Topology::Rect).unwrap();
+        let solution = solve_bidirectional(board);
+        assert_eq!(solution.move_count, solve_shortest(board, false).move_count);
+        assert_eq!(verify_solution(&board, &solution.moves), Ok(()));
+    }
+
+    // Two rows of 128 cows, each next to the last one's barn: 256 moves, one past what fits in a
+    // u8, and the first estimate is cut to 255.
+    #[test]
+    fn informed_searches_count_past_255_moves() {
+        let row = format!("B{}_", "O".repeat(128));
+        let board =
+            LargeBoard::from_string(&format!("130|2|{}{}", row, row), Topology::Rect).unwrap();
+        for solution in [
+            solve_astar(board, movers_not_home, false),
+            solve_ida_star(board, movers_not_home, false),
+        ] {
+            assert!(solution.can_be_solved);
+            assert_eq!(solution.move_count, 256);
+            assert_eq!(verify_solution(&board, &solution.moves), Ok(()));
+        }
+    }
+
+    // a heuristic at its cap adds a constant to every estimate, which only shifts the bounds
+    #[test]
+    fn informed_searches_handle_capped_estimates() {
+        let board = Board::<1>::from_string("4|3|B_PO____O__P", Topology::Rect).unwrap();
+        let capped: Heuristic = |_| u8::MAX;
+        let shortest = solve_shortest(board, false).move_count;
+        assert_eq!(solve_astar(board, capped, false).move_count, shortest);
+        assert_eq!(solve_ida_star(board, capped, false).move_count, shortest);
+    }
+
+    // A board with no solution, and a coloured one with many ways to reach the same boards. IDA*
+    // has to give up on the first without trying every path, and find the second without
+    // re-expanding the boards it reaches again.
+    #[test]
+    fn ida_star_skips_boards_it_has_reached() {
+        let board = Board::<1>::from_string("3|4|P_O_BPPHPOPO", Topology::Rect).unwrap();
+        assert!(!solve_shortest(board, false).can_be_solved);
+        for use_symmetry in [false, true] {
+            assert!(!solve_ida_star(board, line_distance, use_symmetry).can_be_solved);
+        }
+
+        let board = ColouredBoard::from_string("3|4|_PrHOP_rPP_R", Topology::Triangle).unwrap();
+        let shortest = solve_shortest(board, false);
+        let solution = solve_ida_star(board, line_distance, false);
+        assert_eq!(solution.move_count, shortest.move_count);
+        assert_eq!(verify_solution(&board, &solution.moves), Ok(()));
+        assert!(
+            solution.tree_size < 10 * shortest.tree_size,
+            "{}",
+            solution.tree_size
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+use crate::board::{Board, ParseBoardError, Piece};
+use crate::reverse_solver::get_combinations;
+
+// first line of a tablebase file
+const MAGIC: &str = "cows tablebase 1";
+
+// Exact distances to solved for every solvable board on one layout of barns, houses and empty
+// cells, with at most max_cow_count cows and max_person_count people. Boards are stored by their
+// canonical form, so the table also covers the layout's symmetric variants. Tables are for the
+// default Board, so layouts have no coloured herds.
+pub struct Tablebase<const WORDS: usize = 1> {
+    // the layout with no cows or people on it
+    pub layout: Board<WORDS>,
+    pub max_cow_count: u8,
+    pub max_person_count: u8,
+    distances: HashMap<Board<WORDS>, u16>,
+}
+
+// Retrograde analysis: a breadth first search over previous boards that starts from every solved
+// board of the layout at once, so each board is first reached at its distance to the nearest one.
+// Pieces without a goal are never taken off, so solved boards have any number of them, up to the
+// maximum, on any free cells.
+pub fn build_tablebase<const WORDS: usize>(
+    layout: Board<WORDS>,
+    max_cow_count: u8,
+    max_person_count: u8,
+) -> Tablebase<WORDS> {
+    let layout = without_movers(&layout);
+    let mut distances: HashMap<Board<WORDS>, u16> = HashMap::new();
+    let mut board_queue: VecDeque<(Board<WORDS>, u16)> = VecDeque::new();
+
+    for board in solved_boards(&layout, max_cow_count, max_person_count) {
+        if distances.insert(board.canonical(), 0).is_none() {
+            board_queue.push_back((board, 0));
+        }
+    }
+
+    let mut iterations = 0;
+    while let Some((board, move_count)) = board_queue.pop_front() {
+        iterations += 1;
+        if iterations % 100000 == 0 {
+            println!(
+                "Iterations: {}. In board queue: {}",
+                iterations,
+                board_queue.len()
+            );
+        }
+        for (board, _, _, _) in board.get_possible_previous_boards() {
+            if board.count_piece(Piece::Cow) > max_cow_count as usize
+                || board.count_piece(Piece::Person) > max_person_count as usize
+            {
+                continue;
+            }
+            let key = board.canonical();
+            if distances.contains_key(&key) {
+                continue;
+            }
+            distances.insert(key, move_count + 1);
+            board_queue.push_back((board, move_count + 1));
+        }
+    }
+
+    Tablebase {
+        layout,
+        max_cow_count,
+        max_person_count,
+        distances,
+    }
+}
+
+fn without_movers<const WORDS: usize>(board: &Board<WORDS>) -> Board<WORDS> {
+    let mut layout = *board;
+    for i in 0..layout.cell_count() {
+        if layout.get_index(i).is_mover() {
+            layout.set_index(i, Piece::Blank);
+        }
+    }
+    layout
+}
+
+fn solved_boards<const WORDS: usize>(
+    layout: &Board<WORDS>,
+    max_cow_count: u8,
+    max_person_count: u8,
+) -> Vec<Board<WORDS>> {
+    let mut boards = vec![*layout];
+    // cows can only be left on a solved board when there is no barn, people when there is no house
+    for (piece, goal, max_count) in [
+        (Piece::Cow, Piece::Barn, max_cow_count),
+        (Piece::Person, Piece::House, max_person_count),
+    ] {
+        if layout.count_piece(goal) > 0 {
+            continue;
+        }
+        let mut with_piece = Vec::new();
+        for board in boards {
+            let mut blank_indices: Vec<u16> = Vec::new();
+            for i in 0..board.cell_count() {
+                if board.get_index(i) == Piece::Blank {
+                    blank_indices.push(i as u16);
+                }
+            }
+            for count in 0..=(max_count as usize).min(blank_indices.len()) {
+                let mut combinations: Vec<Vec<u16>> = Vec::new();
+                get_combinations(&mut combinations, &blank_indices, count, 0, Vec::new());
+                for combination in combinations {
+                    let mut solved_board = board;
+                    for index in combination {
+                        solved_board.set_index(index as usize, piece);
+                    }
+                    with_piece.push(solved_board);
+                }
+            }
+        }
+        boards = with_piece;
+    }
+    boards
+}
+
+impl<const WORDS: usize> Tablebase<WORDS> {
+    // Whether board is one this table was built for: its layout is the table's or a symmetric
+    // variant of it, and it has no more cows and people than the table allows.
+    pub fn covers(&self, board: &Board<WORDS>) -> bool {
+        board.get_symmetric_variants().iter().any(|variant| {
+            without_movers(variant) == self.layout
+                && variant.count_piece(Piece::Cow) <= self.max_cow_count as usize
+                && variant.count_piece(Piece::Person) <= self.max_person_count as usize
+        })
+    }
+
+    // Moves needed to solve board, or None if it can't be solved or isn't covered by the table.
+    pub fn distance(&self, board: &Board<WORDS>) -> Option<u16> {
+        self.distances.get(&board.canonical()).copied()
+    }
+
+    // number of solvable boards in the table, counting symmetric variants once
+    pub fn len(&self) -> usize {
+        self.distances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty()
+    }
+
+    // The file is a text header of two lines, the magic line and then
+    // "tagged layout\tmax cows\tmax people\tboard count", followed by one binary record per board:
+    // its cells' piece codes packed two to a byte, low half first, then its distance. Tables with a
+    // distance over 255 don't fit the format and aren't saved.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut data = format!(
+            "{}\n{}\t{}\t{}\t{}\n",
+            MAGIC,
+            self.layout.to_tagged_string(),
+            self.max_cow_count,
+            self.max_person_count,
+            self.distances.len()
+        )
+        .into_bytes();
+        let mut entries: Vec<(&Board<WORDS>, &u16)> = self.distances.iter().collect();
+        entries.sort();
+        for (board, &distance) in entries {
+            let distance = u8::try_from(distance).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} is {} moves from solved, tablebase files only hold up to {}",
+                        board.to_tagged_string(),
+                        distance,
+                        u8::MAX
+                    ),
+                )
+            })?;
+            for i in (0..board.cell_count()).step_by(2) {
+                let mut byte = board.get_index(i) as u8;
+                if i + 1 < board.cell_count() {
+                    byte |= (board.get_index(i + 1) as u8) << 4;
+                }
+                data.push(byte);
+            }
+            data.push(distance);
+        }
+        fs::write(path, data)
+    }
+
+    pub fn load(path: &str) -> Result<Tablebase<WORDS>, TablebaseError> {
+        let data = fs::read(path)?;
+        let mut lines = data.splitn(3, |&byte| byte == b'\n');
+        let magic = lines.next().unwrap_or_default();
+        if magic != MAGIC.as_bytes() {
+            return Err(TablebaseError::InvalidHeader(
+                "not a tablebase file".to_string(),
+            ));
+        }
+        let header = lines
+            .next()
+            .ok_or_else(|| TablebaseError::InvalidHeader("missing header line".to_string()))?;
+        let header = std::str::from_utf8(header)
+            .map_err(|_| TablebaseError::InvalidHeader("header is not text".to_string()))?;
+        let records = lines.next().unwrap_or_default();
+
+        let fields: Vec<&str> = header.split('\t').collect();
+        if fields.len() != 4 {
+            return Err(TablebaseError::InvalidHeader(format!(
+                "expected 4 fields, found {}",
+                fields.len()
+            )));
+        }
+        let layout: Board<WORDS> = fields[0].parse().map_err(TablebaseError::InvalidLayout)?;
+        let max_cow_count: u8 = parse_number(fields[1])?;
+        let max_person_count: u8 = parse_number(fields[2])?;
+        let count: usize = parse_number(fields[3])?;
+
+        // every stored board is canonical, so has the canonical layout's shape
+        let shape = layout.canonical();
+        let cells = shape.cell_count();
+        let record_length = cells.div_ceil(2) + 1;
+        if records.len() != count * record_length {
+            return Err(TablebaseError::WrongLength {
+                expected: count * record_length,
+                actual: records.len(),
+            });
+        }
+        let mut distances = HashMap::with_capacity(count);
+        for (record_index, record) in records.chunks(record_length).enumerate() {
+            let mut board = Board::new(shape.width, shape.height, shape.topology, shape.wraps);
+            for i in 0..cells {
+                let code = (record[i / 2] >> (4 * (i % 2))) & 0xf;
+                let piece = *Piece::ALL
+                    .get(code as usize)
+                    .filter(|&&piece| Board::<WORDS>::can_hold(piece))
+                    .ok_or(TablebaseError::InvalidPiece {
+                        record: record_index,
+                        code,
+                    })?;
+                board.set_index(i, piece);
+            }
+            distances.insert(board, u16::from(record[record_length - 1]));
+        }
+
+        Ok(Tablebase {
+            layout,
+            max_cow_count,
+            max_person_count,
+            distances,
+        })
+    }
+}
+
+fn parse_number<T: FromStr>(field: &str) -> Result<T, TablebaseError> {
+    field
+        .parse()
+        .map_err(|_| TablebaseError::InvalidHeader(format!("invalid number '{}'", field)))
+}
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(io::Error),
+    InvalidHeader(String),
+    InvalidLayout(ParseBoardError),
+    // bytes of record data, which don't match the board count in the header
+    WrongLength { expected: usize, actual: usize },
+    InvalidPiece { record: usize, code: u8 },
+}
+
+impl From<io::Error> for TablebaseError {
+    fn from(error: io::Error) -> TablebaseError {
+        TablebaseError::Io(error)
+    }
+}
+
+impl fmt::Display for TablebaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TablebaseError::Io(error) => write!(f, "{}", error),
+            TablebaseError::InvalidHeader(message) => write!(f, "invalid header: {}", message),
+            TablebaseError::InvalidLayout(error) => write!(f, "invalid layout: {}", error),
+            TablebaseError::WrongLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected {} bytes of records, found {}",
+                    expected, actual
+                )
+            }
+            TablebaseError::InvalidPiece { record, code } => {
+                write!(f, "record {} has unknown piece code {}", record, code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Topology;
+
+    // No table small enough to build goes past 255 moves, so one board's distance is set by hand.
+    // Tables hold it, but the file format can't, so saving fails instead of wrapping it.
+    #[test]
+    fn distances_past_255_moves_are_kept_but_not_saved() {
+        let board = Board::<1>::from_string("4|3|B_PO____O__P", Topology::Rect).unwrap();
+        let mut tablebase = build_tablebase(board, 2, 2);
+        assert!(tablebase.covers(&board));
+        assert!(tablebase.distance(&board).is_some());
+        tablebase.distances.insert(board.canonical(), 300);
+        assert_eq!(tablebase.distance(&board), Some(300));
+
+        let path = std::env::temp_dir().join("distances_past_255_moves_are_kept_but_not_saved");
+        let error = tablebase.save(path.to_str().unwrap()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+    }
+}